@@ -0,0 +1,135 @@
+//! Compares spawning all the maps' markers as sprites sharing their icon's texture to the previous
+//! approach, with a mesh, a material and a child sprite per marker. Runs headless, without
+//! rendering, and the current spawning's entities include the materials' clusters. Run with
+//! `cargo bench --bench markers_spawn`.
+
+use std::time::{Duration, Instant};
+
+use bevy::{ecs::system::CommandQueue, prelude::*, sprite::MaterialMesh2dBundle};
+use futures_lite::future::block_on;
+use totk_map::{
+    marker_source::DirMarkerSource,
+    markers::spawn_markers_for_map,
+    resources::{DisplayedMarkers, MapType, Markers, SpawnedMarkers},
+    spatial::MarkersIndex,
+};
+
+struct Report {
+    spawn_time: Duration,
+    entities: usize,
+    meshes: usize,
+    materials: usize,
+}
+
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .add_asset::<Image>()
+        .add_asset::<Mesh>()
+        .add_asset::<ColorMaterial>();
+    app
+}
+
+fn report(app: &App, spawn_time: Duration) -> Report {
+    Report {
+        spawn_time,
+        entities: app.world.entities().len() as usize,
+        meshes: app.world.resource::<Assets<Mesh>>().len(),
+        materials: app.world.resource::<Assets<ColorMaterial>>().len(),
+    }
+}
+
+fn spawn_batched(markers: &Markers) -> Report {
+    let mut app = headless_app();
+    let mut displayed_markers = DisplayedMarkers::default();
+    let mut spawned_markers = SpawnedMarkers::default();
+    let mut markers_index = MarkersIndex::default();
+
+    let start = Instant::now();
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &app.world);
+    for map_type in MapType::iter() {
+        spawn_markers_for_map(
+            &mut commands,
+            app.world.resource::<AssetServer>(),
+            markers,
+            &mut displayed_markers,
+            *map_type,
+            &mut spawned_markers,
+            &mut markers_index,
+        );
+    }
+    queue.apply(&mut app.world);
+    let spawn_time = start.elapsed();
+
+    report(&app, spawn_time)
+}
+
+/// The previous spawning, without the markers' data components
+fn spawn_per_marker_mesh(markers: &Markers) -> Report {
+    let mut app = headless_app();
+
+    let start = Instant::now();
+    let positions = MapType::iter()
+        .iter()
+        .flat_map(|map_type| {
+            let locations = markers
+                .locations(*map_type)
+                .iter()
+                .flat_map(|location| &location.layers)
+                .flat_map(|layer| &layer.markers)
+                .map(|layer_marker| layer_marker.pos);
+            let materials = markers
+                .materials(*map_type)
+                .iter()
+                .flat_map(|material| &material.pos)
+                .map(|pos| pos.truncate());
+            locations.chain(materials)
+        })
+        .collect::<Vec<_>>();
+    let texture = app
+        .world
+        .resource::<AssetServer>()
+        .load::<Image, _>("icons/star.png");
+    app.world
+        .resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+            world.resource_scope(|world, mut materials: Mut<Assets<ColorMaterial>>| {
+                for pos in positions {
+                    world
+                        .spawn(MaterialMesh2dBundle {
+                            mesh: meshes
+                                .add(Mesh::from(shape::Quad::new(Vec2::splat(2.0))))
+                                .into(),
+                            material: materials
+                                .add(ColorMaterial::from(Color::rgba(0.0, 0.0, 0.0, 0.0))),
+                            transform: Transform::from_xyz(pos.y, pos.x, 100.0),
+                            ..default()
+                        })
+                        .with_children(|commands| {
+                            commands.spawn(SpriteBundle {
+                                texture: texture.clone(),
+                                transform: Transform::from_scale(Vec3::splat(0.1)),
+                                ..default()
+                            });
+                        });
+                }
+            });
+        });
+    let spawn_time = start.elapsed();
+
+    report(&app, spawn_time)
+}
+
+fn main() {
+    let markers = block_on(Markers::load(&DirMarkerSource::default())).expect("markers to load");
+
+    for (name, report) in [
+        ("per-marker mesh", spawn_per_marker_mesh(&markers)),
+        ("batched sprites", spawn_batched(&markers)),
+    ] {
+        println!(
+            "{name}: spawned in {:?}, {} entities, {} meshes, {} materials",
+            report.spawn_time, report.entities, report.meshes, report.materials
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Compares the per-frame markers lookups done with a full scan to the ones done with the
+//! spatial index, on the real markers datasets. Run with `cargo bench --bench spatial_index`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use bevy::{math::Rect, prelude::Vec2};
+use futures_lite::future::block_on;
+use totk_map::{
+    marker_source::DirMarkerSource,
+    resources::{MapType, Markers},
+    spatial::QuadTree,
+};
+
+const ITERATIONS: u32 = 1_000;
+
+/// Times `f` over `ITERATIONS` runs, its results are kept alive so that they aren't optimized out
+fn time(mut f: impl FnMut(u32) -> usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let total = (0..ITERATIONS).map(|i| black_box(f(i))).sum();
+    (start.elapsed() / ITERATIONS, total)
+}
+
+fn main() {
+    let markers = block_on(Markers::load(&DirMarkerSource::default())).expect("markers to load");
+
+    for map_type in MapType::iter() {
+        let positions = markers
+            .locations(*map_type)
+            .iter()
+            .flat_map(|location| &location.layers)
+            .flat_map(|layer| &layer.markers)
+            .map(|layer_marker| Vec2::new(layer_marker.pos.y, layer_marker.pos.x))
+            .chain(
+                markers
+                    .materials(*map_type)
+                    .iter()
+                    .flat_map(|material| &material.pos)
+                    .map(|pos| Vec2::new(pos.y, pos.x)),
+            )
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let quad_tree = positions
+            .iter()
+            .enumerate()
+            .map(|(index, pos)| (*pos, index))
+            .collect::<QuadTree<_>>();
+        let build_time = start.elapsed();
+
+        // A zoomed in viewport moving across the map, and the cursor at its center
+        let viewports = (0..ITERATIONS)
+            .map(|i| {
+                let center = Vec2::new(
+                    (i as f32 * 37.0) % 8_000.0 - 4_000.0,
+                    (i as f32 * 53.0) % 8_000.0 - 4_000.0,
+                );
+                Rect::from_center_half_size(center, Vec2::new(480.0, 270.0))
+            })
+            .collect::<Vec<_>>();
+
+        let (scan_rect, scan_rect_total) = time(|i| {
+            let viewport = viewports[i as usize];
+            positions
+                .iter()
+                .filter(|pos| viewport.contains(**pos))
+                .count()
+        });
+        let (index_rect, index_rect_total) =
+            time(|i| quad_tree.query_rect(viewports[i as usize]).len());
+        assert_eq!(scan_rect_total, index_rect_total);
+
+        let (scan_nearest, _) = time(|i| {
+            let cursor = viewports[i as usize].center();
+            positions
+                .iter()
+                .enumerate()
+                .filter(|(_, pos)| pos.distance(cursor) <= 100.0)
+                .min_by(|(_, pos), (_, other_pos)| {
+                    pos.distance_squared(cursor)
+                        .total_cmp(&other_pos.distance_squared(cursor))
+                })
+                .map_or(0, |(index, _)| index)
+        });
+        let (index_nearest, _) = time(|i| {
+            quad_tree
+                .nearest(viewports[i as usize].center(), 100.0)
+                .unwrap_or(0)
+        });
+
+        println!(
+            "{map_type}: {} markers, index built in {build_time:?}",
+            positions.len()
+        );
+        println!(
+            "  viewport: full scan {scan_rect:?}, index {index_rect:?} (x{:.1})",
+            scan_rect.as_secs_f64() / index_rect.as_secs_f64()
+        );
+        println!(
+            "  nearest:  full scan {scan_nearest:?}, index {index_nearest:?} (x{:.1})",
+            scan_nearest.as_secs_f64() / index_nearest.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Integrity of the tiles and icons, used by the `totk check-assets` subcommand.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    markers::{location_icon_path, LOCATION_ICON_PATH, MATERIAL_ICON_PATH},
+    resources::{MapType, Markers, TileFormat, TileLayout, TileLayouts},
+};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AssetProblem {
+    /// The lowest lod's tile isn't found in any format
+    NoTiles {
+        map_type: MapType,
+    },
+    TilesCount {
+        map_type: MapType,
+        lod: u32,
+        expected: usize,
+        found: usize,
+    },
+    MissingTile {
+        path: PathBuf,
+    },
+    UnreadableTile {
+        path: PathBuf,
+        error: String,
+    },
+    /// The tiles of a map don't all have the same size as its lowest lod's tile
+    TileSize {
+        path: PathBuf,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    MissingIcon {
+        path: PathBuf,
+        used_by: String,
+    },
+}
+
+impl Display for AssetProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoTiles { map_type } => write!(f, "no tiles found for {map_type}"),
+            Self::TilesCount {
+                map_type,
+                lod,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{map_type} lod {lod} has {found} tiles instead of {expected}"
+            ),
+            Self::MissingTile { path } => write!(f, "missing tile {}", path.display()),
+            Self::UnreadableTile { path, error } => {
+                write!(f, "unreadable tile {}: {error}", path.display())
+            }
+            Self::TileSize {
+                path,
+                expected: (expected_width, expected_height),
+                found: (width, height),
+            } => write!(
+                f,
+                "tile {} is {width}x{height} instead of {expected_width}x{expected_height}",
+                path.display()
+            ),
+            Self::MissingIcon { path, used_by } => {
+                write!(f, "missing icon {} used by {used_by}", path.display())
+            }
+        }
+    }
+}
+
+/// The tiles checked for a map
+#[derive(Debug, Serialize)]
+pub struct TilesReport {
+    pub map_type: MapType,
+    /// The format's extension
+    pub format: Option<&'static str>,
+    pub tiles: usize,
+    /// Of the lowest lod's tile
+    pub size: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AssetsReport {
+    pub tiles: Vec<TilesReport>,
+    pub icons: usize,
+    pub problems: Vec<AssetProblem>,
+}
+
+impl AssetsReport {
+    /// Checks the tiles of every map, in their layout, and the icons of the markers
+    #[must_use]
+    pub fn check(assets_dir: &Path, markers: &Markers, tile_layouts: &TileLayouts) -> Self {
+        let mut report = Self::default();
+        for map_type in MapType::iter() {
+            report.check_tiles(assets_dir, *map_type, tile_layouts.get(*map_type));
+        }
+        report.check_icons(assets_dir, markers);
+        report
+    }
+
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Every lod's directories must have exactly `tiles_nb²` tiles, all of the same size. The
+    /// layout's format is detected like at startup when it's `None`.
+    pub fn check_tiles(&mut self, assets_dir: &Path, map_type: MapType, layout: &TileLayout) {
+        let Some(format) = layout
+            .format
+            .or_else(|| layout.detect_format(map_type, assets_dir))
+        else {
+            self.problems.push(AssetProblem::NoTiles { map_type });
+            self.tiles.push(TilesReport {
+                map_type,
+                format: None,
+                tiles: 0,
+                size: None,
+            });
+            return;
+        };
+
+        let mut tiles = 0;
+        let mut size = None;
+        for lod in map_type.lods() {
+            let expected = lod.tiles_nb().pow(2) as usize;
+            // The lod's tiles can be spread in several directories, e.g. by column
+            let dirs = (0..lod.tiles_nb())
+                .flat_map(|x_idx| (0..lod.tiles_nb()).map(move |y_idx| (x_idx, y_idx)))
+                .filter_map(|(x_idx, y_idx)| {
+                    let tile = layout.path_with_format(format, map_type, lod, x_idx, y_idx);
+                    tile.parent().map(|dir| assets_dir.join(dir))
+                })
+                .collect::<BTreeSet<_>>();
+            let found = dirs
+                .iter()
+                .map(|dir| count_files(dir, format.extension()))
+                .sum();
+            if found != expected {
+                self.problems.push(AssetProblem::TilesCount {
+                    map_type,
+                    lod: lod.value(),
+                    expected,
+                    found,
+                });
+            }
+
+            for x_idx in 0..lod.tiles_nb() {
+                for y_idx in 0..lod.tiles_nb() {
+                    let path = assets_dir
+                        .join(layout.path_with_format(format, map_type, lod, x_idx, y_idx));
+                    if !path.is_file() {
+                        self.problems.push(AssetProblem::MissingTile { path });
+                        continue;
+                    }
+                    tiles += 1;
+                    match tile_dimensions(&path, format) {
+                        Ok(found) => {
+                            let expected = *size.get_or_insert(found);
+                            if found != expected {
+                                self.problems.push(AssetProblem::TileSize {
+                                    path,
+                                    expected,
+                                    found,
+                                });
+                            }
+                        }
+                        Err(error) => self.problems.push(AssetProblem::UnreadableTile {
+                            path,
+                            error: error.to_string(),
+                        }),
+                    }
+                }
+            }
+        }
+
+        self.tiles.push(TilesReport {
+            map_type,
+            format: Some(format.extension()),
+            tiles,
+            size,
+        });
+    }
+
+    /// The icons of the locations' layers and the materials' icon. The completed variants are
+    /// optional, the completed markers' icon is dimmed without one.
+    pub fn check_icons(&mut self, assets_dir: &Path, markers: &Markers) {
+        // Sorted for a stable report, with the first category using each icon
+        let mut icons = BTreeMap::new();
+        // The icons used when the markers don't have one
+        icons.insert(LOCATION_ICON_PATH.to_string(), "locations".to_string());
+        icons.insert(MATERIAL_ICON_PATH.to_string(), "materials".to_string());
+        for map_type in MapType::iter() {
+            for location in markers.locations(*map_type) {
+                for layer in &location.layers {
+                    let icon_path = location_icon_path(layer);
+                    let used_by = format!("{map_type} {}", location.name);
+                    icons.entry(icon_path).or_insert(used_by);
+                }
+            }
+        }
+
+        self.icons = icons.len();
+        for (icon_path, used_by) in icons {
+            let path = assets_dir.join(icon_path);
+            if !path.is_file() {
+                self.problems
+                    .push(AssetProblem::MissingIcon { path, used_by });
+            }
+        }
+    }
+}
+
+impl Display for AssetsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "tiles")?;
+        for tiles in &self.tiles {
+            write!(
+                f,
+                "  {}: {} tiles, {}",
+                tiles.map_type,
+                tiles.tiles,
+                tiles.format.unwrap_or("no format")
+            )?;
+            if let Some((width, height)) = tiles.size {
+                write!(f, ", {width}x{height}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "icons")?;
+        writeln!(f, "  {} icons", self.icons)?;
+        writeln!(f, "problems")?;
+        if self.problems.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "  {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+fn count_files(dir: &Path, extension: &str) -> usize {
+    fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == extension))
+            .count()
+    })
+}
+
+fn tile_dimensions(path: &Path, format: TileFormat) -> anyhow::Result<(u32, u32)> {
+    if format != TileFormat::Ktx2 {
+        return Ok(image::image_dimensions(path)?);
+    }
+
+    // The identifier, the format and the type size, followed by the width and height
+    let mut header = [0; 28];
+    File::open(path)?.read_exact(&mut header)?;
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            header[offset],
+            header[offset + 1],
+            header[offset + 2],
+            header[offset + 3],
+        ])
+    };
+    Ok((read_u32(20), read_u32(24)))
+}
@@ -3,25 +3,64 @@ use bevy::{
     math::vec2,
     prelude::*,
     render::camera::CameraProjection,
+    utils::HashMap,
     window::PrimaryWindow,
 };
-use bevy_mod_raycast::RaycastSource;
 use bevy_pancam::PanCam;
 
-use crate::{picking::RaycastSet, resources::MAP_SIZE_PX, ui::egui_is_hovered};
+use crate::{
+    resources::{Crosshair, MapType, CROSSHAIR_DURATION},
+    ui::egui_is_hovered,
+};
+
+/// Past this distance in pixels, the cursor movement is a pan and not a click
+const CLICK_MAX_DISTANCE: f32 = 4.0;
+/// Half the crosshair's width, in pixels
+const CROSSHAIR_SIZE: f32 = 16.0;
+const CROSSHAIR_COLOR: Color = Color::RED;
 
 pub struct CameraPlugin;
 
 #[derive(Component)]
 pub struct MainCamera;
 
+/// Sent when a mouse button is pressed and released on the map without panning it
+#[derive(Debug, Event)]
+pub struct MapClicked {
+    pub button: MouseButton,
+    pub world_position: Vec2,
+}
+
+/// Centers the main camera on a position, switching to its map first if needed
+#[derive(Debug, Event)]
+pub struct FlyTo {
+    pub map_type: MapType,
+    /// In world coordinates
+    pub position: Vec2,
+    pub scale: f32,
+}
+
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(PanCamPlugin).add_systems(Startup, camera);
+        app.add_plugins(PanCamPlugin)
+            .add_event::<MapClicked>()
+            .add_event::<FlyTo>()
+            .add_systems(Startup, camera)
+            .add_systems(
+                Update,
+                (
+                    detect_map_clicks.run_if(not(egui_is_hovered)),
+                    fly_to,
+                    update_camera_bounds.run_if(resource_changed::<MapType>()),
+                    draw_crosshair.run_if(resource_exists::<Crosshair>()),
+                ),
+            );
     }
 }
 
-fn camera(mut commands: Commands) {
+#[allow(clippy::needless_pass_by_value)]
+fn camera(mut commands: Commands, map_type: Res<MapType>) {
+    let bounds = map_type.info().camera_bounds;
     commands
         .spawn(Camera2dBundle {
             projection: OrthographicProjection {
@@ -33,18 +72,117 @@ fn camera(mut commands: Commands) {
             ..default()
         })
         .insert(PanCam {
-            min_x: Some(-(MAP_SIZE_PX / 2.0) * 4.0),
-            max_x: Some(MAP_SIZE_PX / 2.0 * 4.0),
-            min_y: Some(-(MAP_SIZE_PX / 2.0) * 4.0),
-            max_y: Some(MAP_SIZE_PX / 2.0 * 4.0),
+            min_x: Some(bounds.min.x),
+            max_x: Some(bounds.max.x),
+            min_y: Some(bounds.min.y),
+            max_y: Some(bounds.max.y),
             min_scale: 0.3,
             max_scale: Some(40.0),
             ..default()
         })
-        .insert(RaycastSource::<RaycastSet>::new())
         .insert(MainCamera);
 }
 
+/// The maps may have different bounds
+#[allow(clippy::needless_pass_by_value)]
+fn update_camera_bounds(map_type: Res<MapType>, mut camera: Query<&mut PanCam, With<MainCamera>>) {
+    let bounds = map_type.info().camera_bounds;
+    for mut pan_cam in &mut camera {
+        pan_cam.min_x = Some(bounds.min.x);
+        pan_cam.max_x = Some(bounds.max.x);
+        pan_cam.min_y = Some(bounds.min.y);
+        pan_cam.max_y = Some(bounds.max.y);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn detect_map_clicks(
+    mouse_buttons: Res<Input<MouseButton>>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut map_clicked: EventWriter<MapClicked>,
+    mut pressed_positions: Local<HashMap<MouseButton, Vec2>>,
+) {
+    let Some(cursor_position) = primary_window.single().cursor_position() else {
+        return;
+    };
+    for button in mouse_buttons.get_just_pressed() {
+        pressed_positions.insert(*button, cursor_position);
+    }
+    for button in mouse_buttons.get_just_released() {
+        let Some(pressed_position) = pressed_positions.remove(button) else {
+            continue;
+        };
+        if pressed_position.distance(cursor_position) > CLICK_MAX_DISTANCE {
+            continue;
+        }
+        let Ok((camera, camera_transform)) = camera.get_single() else {
+            continue;
+        };
+        if let Some(world_position) = camera.viewport_to_world_2d(camera_transform, cursor_position)
+        {
+            map_clicked.send(MapClicked {
+                button: *button,
+                world_position,
+            });
+        }
+    }
+}
+
+fn fly_to(
+    mut fly_to: EventReader<FlyTo>,
+    mut map_type: ResMut<MapType>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let Some(fly_to) = fly_to.iter().last() else {
+        return;
+    };
+    if *map_type != fly_to.map_type {
+        *map_type = fly_to.map_type;
+    }
+    let Ok((mut transform, mut projection)) = camera.get_single_mut() else {
+        return;
+    };
+    transform.translation.x = fly_to.position.x;
+    transform.translation.y = fly_to.position.y;
+    projection.scale = fly_to.scale;
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_crosshair(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    crosshair: Res<Crosshair>,
+    map_type: Res<MapType>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+    time: Res<Time>,
+) {
+    if time.elapsed_seconds() - crosshair.created_at >= CROSSHAIR_DURATION {
+        commands.remove_resource::<Crosshair>();
+        return;
+    }
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+    if crosshair.map_type != *map_type {
+        return;
+    }
+    // The same size on screen whatever the zoom
+    let size = CROSSHAIR_SIZE * projection.scale;
+    let position = crosshair.position;
+    gizmos.line_2d(
+        position - Vec2::X * size,
+        position + Vec2::X * size,
+        CROSSHAIR_COLOR,
+    );
+    gizmos.line_2d(
+        position - Vec2::Y * size,
+        position + Vec2::Y * size,
+        CROSSHAIR_COLOR,
+    );
+    gizmos.circle_2d(position, size / 2.0, CROSSHAIR_COLOR);
+}
+
 // The following is copied from `bevy_pancam` because of https://github.com/johanhelsing/bevy_pancam/issues/37
 // The solution is inspired by the comments in https://github.com/mvlabat/bevy_egui/issues/47 and is rather rudimentary
 // but works well so far.
@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+use bevy::{math::Rect, prelude::*, sprite::Anchor, utils::HashMap};
+
+use crate::{
+    camera::{FlyTo, MainCamera, MapClicked},
+    markers::{marker_at, MARKERS_Z},
+    resources::{AppState, Lod, MapType},
+    ruler::ruler_is_active,
+    spatial::MarkersIndex,
+};
+
+/// Materials are clustered up to this lod, included
+pub const CLUSTER_MAX_LOD: u32 = 2;
+/// Grid cells per tile side, the cells are nested from one lod to the next like the tiles
+const CELLS_PER_TILE: f32 = 8.0;
+/// A cell with fewer markers doesn't make a cluster
+const CLUSTER_MIN_SIZE: usize = 2;
+/// Margin around the members when zooming in to a cluster
+const FIT_MARGIN: f32 = 1.2;
+const BADGE_COLOR: Color = Color::rgb(1.0, 0.85, 0.0);
+const BADGE_FONT_SIZE: f32 = 24.0;
+/// From the icon's center, in the icon's pixels
+const BADGE_OFFSET: f32 = 8.0;
+
+pub struct ClustersPlugin;
+
+impl Plugin for ClustersPlugin {
+    fn build(&self, app: &mut App) {
+        // The clicks add the ruler's points while it's active
+        app.add_systems(
+            Update,
+            zoom_to_cluster
+                .run_if(in_state(AppState::Ready))
+                .run_if(not(ruler_is_active)),
+        );
+    }
+}
+
+/// Markers of the same category grouped in a grid cell
+#[derive(Debug)]
+pub struct Cluster {
+    /// Indexes of the clustered positions
+    pub members: Vec<usize>,
+    pub center: Vec2,
+    pub bounds: Rect,
+}
+
+/// The lods at which the map's materials are clustered, up to [`CLUSTER_MAX_LOD`] and always
+/// below the map's most detailed lod so that the clusters split before it
+#[must_use]
+pub fn cluster_lods(map_type: MapType) -> Range<u32> {
+    map_type.min_lod().value()..(CLUSTER_MAX_LOD + 1).min(map_type.max_lod().value())
+}
+
+/// Groups the positions, in world coordinates, by cells of [`cell_size`] at the map's `lod`
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+#[must_use]
+pub fn grid_clusters(positions: &[Vec2], map_type: MapType, lod: Lod) -> Vec<Cluster> {
+    let cell_size = cell_size(map_type, lod);
+    let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::default();
+    for (index, pos) in positions.iter().enumerate() {
+        let cell = (*pos / cell_size).floor();
+        cells
+            .entry((cell.x as i32, cell.y as i32))
+            .or_default()
+            .push(index);
+    }
+
+    let mut clusters = cells
+        .into_values()
+        .filter(|members| members.len() >= CLUSTER_MIN_SIZE)
+        .map(|members| {
+            let first = positions[members[0]];
+            let bounds = members
+                .iter()
+                .fold(Rect::from_corners(first, first), |bounds, index| {
+                    bounds.union_point(positions[*index])
+                });
+            let center =
+                members.iter().map(|index| positions[*index]).sum::<Vec2>() / members.len() as f32;
+            Cluster {
+                members,
+                center,
+                bounds,
+            }
+        })
+        .collect::<Vec<_>>();
+    // The cells are in a random order
+    clusters.sort_by_key(|cluster| cluster.members[0]);
+    clusters
+}
+
+/// Side of the clustering grid cells at the map's `lod`, halved at each lod
+#[must_use]
+pub fn cell_size(map_type: MapType, lod: Lod) -> f32 {
+    lod.tile_px_size(map_type) / CELLS_PER_TILE
+}
+
+#[derive(Component)]
+pub struct MarkerCluster {
+    pub map_type: MapType,
+    /// The markers category
+    pub name: String,
+    /// The only lod at which the cluster is displayed
+    pub lod: u32,
+    pub count: usize,
+    /// Of the members, in world coordinates
+    pub bounds: Rect,
+}
+
+/// Spawns a hidden cluster sprite with its count badge, scaled like the markers' sprites
+pub fn spawn_cluster(
+    commands: &mut Commands,
+    texture: Handle<Image>,
+    map_type: MapType,
+    name: &str,
+    lod: u32,
+    cluster: &Cluster,
+) -> Entity {
+    let count = cluster.members.len();
+    // The clusters are above the markers they replace, and the badge, in the icon's pixels, is
+    // at its top right corner
+    commands
+        .spawn(SpriteBundle {
+            texture,
+            transform: Transform::from_translation(cluster.center.extend(MARKERS_Z + 10.0)),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|commands| {
+            commands.spawn(Text2dBundle {
+                text: Text::from_section(
+                    count.to_string(),
+                    TextStyle {
+                        font_size: BADGE_FONT_SIZE,
+                        color: BADGE_COLOR,
+                        ..default()
+                    },
+                ),
+                text_anchor: Anchor::BottomLeft,
+                transform: Transform::from_xyz(BADGE_OFFSET, BADGE_OFFSET, 1.0),
+                ..default()
+            });
+        })
+        .insert(MarkerCluster {
+            map_type,
+            name: name.to_string(),
+            lod,
+            count,
+            bounds: cluster.bounds,
+        })
+        .id()
+}
+
+/// The clicked clusters are zoomed in, just enough to see all their members split apart
+#[allow(clippy::needless_pass_by_value)]
+fn zoom_to_cluster(
+    mut map_clicked: EventReader<MapClicked>,
+    map_type: Res<MapType>,
+    markers_index: Res<MarkersIndex>,
+    clickable_markers: Query<(&Visibility, &Transform)>,
+    clusters: Query<&MarkerCluster>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+    mut fly_to: EventWriter<FlyTo>,
+) {
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+    let Some(cluster) = map_clicked
+        .iter()
+        .filter(|map_clicked| map_clicked.button == MouseButton::Left)
+        .filter_map(|map_clicked| {
+            marker_at(
+                &markers_index,
+                *map_type,
+                map_clicked.world_position,
+                projection.scale,
+                &clickable_markers,
+            )
+        })
+        .last()
+        .and_then(|entity| clusters.get(entity).ok())
+    else {
+        return;
+    };
+
+    // The projection's area is the window's size multiplied by its scale
+    let window_size = projection.area.size() / projection.scale;
+    let fit_scale = (cluster.bounds.size() * FIT_MARGIN / window_size).max_element();
+    // At worst, the next lod is reached so that the cluster splits, and at best the most detailed
+    let split_scale = Lod::new(cluster.lod + 1).scale(cluster.map_type);
+    let min_scale = cluster.map_type.max_lod().scale(cluster.map_type);
+    debug!(
+        target: "clusters",
+        "name={} count={} fit_scale={fit_scale}",
+        cluster.name,
+        cluster.count,
+    );
+    fly_to.send(FlyTo {
+        map_type: cluster.map_type,
+        position: cluster.bounds.center(),
+        scale: fit_scale.min(split_scale).max(min_scale),
+    });
+}
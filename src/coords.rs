@@ -0,0 +1,130 @@
+//! The game's coordinates, as found in the markers' JSON files, and Bevy's world coordinates, in
+//! which the maps are centered on the origin.
+
+use std::str::FromStr;
+
+use bevy::{
+    math::Rect,
+    prelude::{Vec2, Vec3},
+};
+
+use crate::resources::{Lod, MapType, TileKey};
+
+/// A position in the game, `x` goes east and `z` north like on the in-game map
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GameCoord {
+    pub x: f32,
+    pub z: f32,
+    pub elevation: f32,
+}
+
+impl GameCoord {
+    #[must_use]
+    pub fn new(x: f32, z: f32, elevation: f32) -> Self {
+        Self { x, z, elevation }
+    }
+
+    /// From the `[z, x]` coordinates of the JSON files, and the marker's elevation
+    #[must_use]
+    pub fn from_json(coords: Vec2, elevation: f32) -> Self {
+        Self::new(coords.y, coords.x, elevation)
+    }
+
+    /// From the `[z, x, elevation]` coordinates of the materials' JSON files
+    #[must_use]
+    pub fn from_material_pos(coords: Vec3) -> Self {
+        Self::from_json(coords.truncate(), coords.z)
+    }
+
+    /// The inverse of [`GameCoord::from_json`], without the elevation
+    #[must_use]
+    pub fn json_coords(self) -> Vec2 {
+        Vec2::new(self.z, self.x)
+    }
+
+    #[must_use]
+    pub fn to_world(self) -> WorldPos {
+        WorldPos(Vec2::new(self.x, self.z))
+    }
+}
+
+/// Coordinates as exchanged by the players, `x, z` or `x, y, z` with `y` the elevation, separated
+/// by commas or spaces
+impl FromStr for GameCoord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| format!("{value} isn't a number"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x, z] => Ok(Self::new(x, z, 0.0)),
+            [x, y, z] => Ok(Self::new(x, z, y)),
+            _ => Err(format!(
+                "expected x, z or x, y, z, found {} values",
+                values.len()
+            )),
+        }
+    }
+}
+
+/// A position in Bevy's world space, the y axis goes up
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WorldPos(pub Vec2);
+
+impl WorldPos {
+    /// Of the JSON files' `[z, x]` coordinates that have no elevation, e.g. the markers' paths
+    #[must_use]
+    pub fn from_json(coords: Vec2) -> Self {
+        GameCoord::from_json(coords, 0.0).to_world()
+    }
+
+    /// The inverse of [`GameCoord::to_world`], the elevation isn't known from the map
+    #[must_use]
+    pub fn to_game(self, elevation: f32) -> GameCoord {
+        GameCoord::new(self.0.x, self.0.y, elevation)
+    }
+
+    /// The map's tile containing this position at `lod`, the tiles' rows go down. The positions
+    /// outside of the map are in the closest tile.
+    #[must_use]
+    pub fn tile(self, map_type: MapType, lod: Lod) -> TileKey {
+        TileKey {
+            map_type,
+            lod,
+            x_idx: lod.index(self.0.x, map_type),
+            y_idx: lod.index(-self.0.y, map_type),
+        }
+    }
+}
+
+impl From<GameCoord> for WorldPos {
+    fn from(coord: GameCoord) -> Self {
+        coord.to_world()
+    }
+}
+
+impl From<WorldPos> for Vec2 {
+    fn from(pos: WorldPos) -> Self {
+        pos.0
+    }
+}
+
+/// The area covered by the tile, in world coordinates
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn tile_bounds(key: TileKey) -> Rect {
+    let half_size = key.map_type.size_px() / 2.0;
+    let tile_px_size = key.lod.tile_px_size(key.map_type);
+    let min_x = -half_size + key.x_idx as f32 * tile_px_size;
+    let max_y = half_size - key.y_idx as f32 * tile_px_size;
+    Rect::new(min_x, max_y - tile_px_size, min_x + tile_px_size, max_y)
+}
@@ -0,0 +1,208 @@
+//! Reloading of the markers' JSON files when they are edited, on desktop. The markers of the
+//! reloaded map are spawned again, the filters, the selection and the camera are kept. The added
+//! locations are displayed.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use bevy::{
+    prelude::*,
+    utils::{synccell::SyncCell, HashMap, HashSet},
+};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    clusters::MarkerCluster,
+    marker_source::{MarkerSource, MarkersFileKind, MarkersFormat},
+    markers::{spawn_map_markers, MarkerSprite},
+    resources::{
+        AppState, DisplayedMarkers, FocusedMarkers, MapType, Markers, Notifications, SpawnedMarkers,
+    },
+    spatial::MarkersIndex,
+    types::Location,
+};
+
+/// Seconds without changes before a file is reloaded, editors often write it in several steps
+const RELOAD_DELAY: f32 = 0.3;
+
+pub struct MarkersHotReloadPlugin {
+    /// Contains the maps' files, like the assets' markers directory
+    pub dir: PathBuf,
+}
+
+impl MarkersHotReloadPlugin {
+    /// Watches the source's JSON files, `None` if it has none
+    #[must_use]
+    pub fn for_source(source: &dyn MarkerSource) -> Option<Self> {
+        source.json_dir().map(|dir| Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+}
+
+impl Plugin for MarkersHotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            // The receiver is only dropped with the app
+            let _ = sender.send(event);
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(&self.dir, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        let watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                warn!(
+                    "the markers won't be reloaded, couldn't watch {}: {err}",
+                    self.dir.display()
+                );
+                return;
+            }
+        };
+
+        app.init_resource::<Notifications>()
+            .insert_resource(MarkersWatcher {
+                dir: self.dir.clone(),
+                _watcher: watcher,
+                events: SyncCell::new(receiver),
+                changed: HashMap::new(),
+            })
+            .add_systems(Update, reload_markers.run_if(in_state(AppState::Ready)));
+    }
+}
+
+#[derive(Resource)]
+struct MarkersWatcher {
+    dir: PathBuf,
+    /// Stops watching when dropped
+    _watcher: RecommendedWatcher,
+    events: SyncCell<Receiver<notify::Result<Event>>>,
+    /// The changed files, with the time of their last change
+    changed: HashMap<(MapType, MarkersFileKind), f32>,
+}
+
+/// The markers' file at `path`, of any of the maps
+fn markers_file(path: &Path) -> Option<(MapType, MarkersFileKind)> {
+    MapType::iter().iter().find_map(|map_type| {
+        MarkersFileKind::iter()
+            .iter()
+            .find(|kind| path.ends_with(MarkersFormat::Json.path(*map_type, **kind)))
+            .map(|kind| (*map_type, *kind))
+    })
+}
+
+/// Only changes the markers if the file is valid, returns the names of the added locations
+fn reload_file(
+    markers: &mut ResMut<Markers>,
+    path: &Path,
+    map_type: MapType,
+    kind: MarkersFileKind,
+) -> anyhow::Result<Vec<String>> {
+    let content = std::fs::read(path)?;
+    match kind {
+        MarkersFileKind::Locations => {
+            let locations: Vec<Location> = serde_json::from_slice(&content)?;
+            let added_names = {
+                let previous_names = markers
+                    .locations(map_type)
+                    .iter()
+                    .map(|location| &location.name)
+                    .collect::<HashSet<_>>();
+                locations
+                    .iter()
+                    .filter(|location| !previous_names.contains(&location.name))
+                    .map(|location| location.name.clone())
+                    .collect()
+            };
+            *markers.locations_mut(map_type) = locations;
+            Ok(added_names)
+        }
+        MarkersFileKind::Materials => {
+            let materials = serde_json::from_slice(&content)?;
+            *markers.materials_mut(map_type) = materials;
+            Ok(Vec::new())
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
+fn reload_markers(
+    mut commands: Commands,
+    assets_server: Res<AssetServer>,
+    mut watcher: ResMut<MarkersWatcher>,
+    mut markers: ResMut<Markers>,
+    spawned_markers: Res<SpawnedMarkers>,
+    mut markers_index: ResMut<MarkersIndex>,
+    mut focused_markers: ResMut<FocusedMarkers>,
+    mut displayed_markers: ResMut<DisplayedMarkers>,
+    mut notifications: ResMut<Notifications>,
+    markers_entities: Query<(Entity, AnyOf<(&MarkerSprite, &MarkerCluster)>)>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let watcher = &mut *watcher;
+    for event in watcher.events.get().try_iter() {
+        match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_) | EventKind::Remove(_)) => {
+                for file in event.paths.iter().filter_map(|path| markers_file(path)) {
+                    watcher.changed.insert(file, now);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => warn!("markers watcher: {err}"),
+        }
+    }
+
+    let ready = watcher
+        .changed
+        .iter()
+        .filter(|(_, changed_at)| now - **changed_at >= RELOAD_DELAY)
+        .map(|(file, _)| *file)
+        .collect::<Vec<_>>();
+    let mut reloaded_maps = HashSet::new();
+    for (map_type, kind) in ready {
+        watcher.changed.remove(&(map_type, kind));
+        let path = watcher.dir.join(MarkersFormat::Json.path(map_type, kind));
+        // The previous markers are kept until the file is fixed
+        match reload_file(&mut markers, &path, map_type, kind) {
+            Ok(added_locations) => {
+                info!(target: "markers", "map={map_type} {kind} reloaded");
+                // Otherwise the added locations would be hidden, the other filters are kept
+                displayed_markers.add_missing_from(map_type, added_locations);
+                notifications.info(format!("{} reloaded", path.display()), now);
+                reloaded_maps.insert(map_type);
+            }
+            Err(err) => {
+                error!("couldn't reload {}: {err}", path.display());
+                notifications.error(
+                    format!("{} couldn't be reloaded: {err}", path.display()),
+                    now,
+                );
+            }
+        }
+    }
+
+    // The maps that were never displayed are spawned with the new markers when they are
+    for map_type in reloaded_maps
+        .into_iter()
+        .filter(|map_type| spawned_markers.is_spawned(*map_type))
+    {
+        for (entity, (marker, cluster)) in &markers_entities {
+            let marker_map_type = marker
+                .map(|marker| marker.map_type)
+                .or(cluster.map(|cluster| cluster.map_type));
+            if marker_map_type == Some(map_type) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        focused_markers.markers_mut().clear();
+        markers_index.insert(
+            map_type,
+            spawn_map_markers(&mut commands, &assets_server, &markers, map_type),
+        );
+    }
+}
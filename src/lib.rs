@@ -7,51 +7,97 @@ use bevy_egui::EguiPlugin;
 use bevy_svg::prelude::*;
 
 use crate::{
-    camera::CameraPlugin, lod::LodPlugin, maps::MapsPlugin, markers::MarkersPlugin,
-    picking::PickingPlugin, resources::Options, ui::UiPlugin,
+    camera::CameraPlugin, clusters::ClustersPlugin, lod::LodPlugin, maps::MapsPlugin,
+    markers::MarkersPlugin, pins::PinsPlugin, resources::Options, ruler::RulerPlugin,
+    search::SearchPlugin, ui::UiPlugin,
 };
 
+pub mod assets_check;
 pub mod camera;
+pub mod clusters;
+pub mod coords;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hot_reload;
 pub mod lod;
 pub mod maps;
+pub mod marker_binary;
+pub mod marker_source;
 pub mod markers;
-pub mod picking;
+pub mod pins;
+pub mod registry;
 pub mod resources;
+pub mod ruler;
+pub mod search;
+pub mod spatial;
+pub mod storage;
+pub mod tiles;
 pub mod types;
 pub mod ui;
+pub mod validation;
 
 pub fn run(options: Options) {
     let canvas = options.canvas.clone();
-    App::new()
-        .insert_resource(options)
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "Tears of the Kingdom - Map".to_string(),
-                    fit_canvas_to_parent: true,
-                    canvas,
-                    ..default()
-                }),
+    let tile_budget = options.tile_budget;
+    let tile_prefetch = options.tile_prefetch;
+    let tile_layouts = options.tile_layouts.clone();
+    let markers_plugin = options
+        .markers_source
+        .clone()
+        .map_or_else(MarkersPlugin::default, MarkersPlugin::new);
+    #[cfg(not(target_arch = "wasm32"))]
+    let markers_hot_reload_plugin =
+        hot_reload::MarkersHotReloadPlugin::for_source(&*markers_plugin.source);
+    let mut app = App::new();
+    app.insert_resource(options).add_plugins((
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Tears of the Kingdom - Map".to_string(),
+                fit_canvas_to_parent: true,
+                canvas,
                 ..default()
             }),
-            FrameTimeDiagnosticsPlugin,
-            EguiPlugin,
-            UiPlugin,
-            PickingPlugin,
-            CameraPlugin,
-            SvgPlugin,
-            LodPlugin::default(),
-            MapsPlugin,
-            MarkersPlugin,
-        ))
-        .run();
+            ..default()
+        }),
+        FrameTimeDiagnosticsPlugin,
+        EguiPlugin,
+        UiPlugin,
+        CameraPlugin,
+        SvgPlugin,
+        LodPlugin::default(),
+        MapsPlugin {
+            tile_budget,
+            tile_prefetch,
+            tile_layouts,
+        },
+        markers_plugin,
+        ClustersPlugin,
+        PinsPlugin,
+        RulerPlugin,
+        SearchPlugin,
+    ));
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(markers_hot_reload_plugin) = markers_hot_reload_plugin {
+        app.add_plugins(markers_hot_reload_plugin);
+    }
+    app.run();
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen::prelude::wasm_bindgen(js_name = "run")]
-pub fn run_wasm(canvas: String, debug_display: bool) {
+pub fn run_wasm(canvas: String, debug_display: bool, tile_format: Option<String>) {
+    // The tiles can't be listed to detect their format, which is JPEG by default
+    let mut tile_layouts = resources::TileLayouts::default();
+    if let Some(format) = tile_format.and_then(|format| format.parse().ok()) {
+        for map_type in resources::MapType::iter() {
+            tile_layouts.get_mut(*map_type).format = Some(format);
+        }
+    }
     run(Options {
         canvas: Some(canvas),
         debug_display,
+        tile_budget: resources::TileBudget::default(),
+        tile_prefetch: resources::TilePrefetch::default(),
+        tile_layouts,
+        markers_source: None,
     });
 }
@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use crate::{camera::MainCamera, resources::Lod};
+use crate::{
+    camera::MainCamera,
+    resources::{Lod, MapType},
+};
 
 pub struct LodPlugin {
     lod: Lod,
@@ -23,11 +26,12 @@ impl Plugin for LodPlugin {
 fn update_lod(
     mut current_lod: ResMut<Lod>,
     camera: Query<&OrthographicProjection, With<MainCamera>>,
+    map_type: Res<MapType>,
 ) {
     let Ok(projection) = camera.get_single() else {
         return;
     };
-    let new_lod = Lod::from_scale(projection.scale);
+    let new_lod = Lod::from_scale(projection.scale, *map_type);
     if new_lod != *current_lod {
         *current_lod = new_lod;
     }
@@ -2,26 +2,316 @@
 #![deny(clippy::pedantic)]
 #![allow(clippy::module_name_repetitions)]
 
-use clap::Parser;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::bail;
+use clap::{Parser, Subcommand, ValueEnum};
+use futures_lite::future::block_on;
+use image::imageops::FilterType;
+use totk_map::{
+    assets_check::AssetsReport,
+    marker_binary::{compile_dir, Compression},
+    marker_source::{DirMarkerSource, MarkersFormat},
+    registry::MapRegistry,
+    resources::{MapType, Markers, TileFormat, TileLayouts},
+    tiles::{build_pyramid, PyramidOptions},
+    validation::{MarkersFile, MarkersValidation},
+};
 
 #[derive(Parser, Debug, bevy::prelude::Resource)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[clap(short, long, action)]
     debug_display: bool,
+    /// Memory budget of the loaded map tiles, in megabytes
+    #[clap(long, default_value_t = 256)]
+    tile_cache_mb: usize,
+    /// Width, in tiles, of the ring of hidden tiles loaded around the visible ones
+    #[clap(long, default_value_t = 1)]
+    tile_prefetch_ring: u32,
+    /// Path of the tiles in the assets, for all the maps or prefixed by a map, e.g.
+    /// `surface={root}/{z}/{x}/{y}.{ext}` with `{root}` the map's tile root
+    #[clap(long)]
+    tile_template: Vec<PerMap<String>>,
+    /// jpg, png, webp or ktx2, for all the maps or prefixed by a map, e.g. `sky=webp`. Detected
+    /// from the assets by default.
+    #[clap(long)]
+    tile_format: Vec<PerMap<TileFormat>>,
+    /// Directory of the markers' files, laid out like `assets/markers`
+    #[clap(long, default_value = "assets/markers")]
+    markers_dir: PathBuf,
+    /// Encoding of the markers' files, the binary ones are compiled by `compile-markers`. Only the
+    /// JSON files are reloaded when they change.
+    #[clap(long, value_enum, default_value_t = Format::Json)]
+    markers_format: Format,
+}
+
+/// A value for all the maps, or for the map prefixing it
+#[derive(Debug, Clone)]
+struct PerMap<T> {
+    map_type: Option<MapType>,
+    value: T,
+}
+
+impl<T: FromStr> FromStr for PerMap<T>
+where
+    T::Err: Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (map_type, value) = match s.split_once('=') {
+            Some((map_type, value)) => (Some(map_type.parse()?), value),
+            None => (None, s),
+        };
+        Ok(Self {
+            map_type,
+            value: value.parse().map_err(|err: T::Err| err.to_string())?,
+        })
+    }
+}
+
+impl<T> PerMap<T> {
+    fn map_types(&self) -> &[MapType] {
+        self.map_type
+            .as_ref()
+            .map_or(MapType::iter(), std::slice::from_ref)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the map tiles
+    Tiles {
+        #[command(subcommand)]
+        command: TilesCommand,
+    },
+    /// Check that all the tiles and the markers' icons are in the assets, the tiles in the layout
+    /// given by `--tile-template` and `--tile-format`, the markers read from `--markers-dir`
+    CheckAssets {
+        /// Print the report as JSON
+        #[clap(long, action)]
+        json: bool,
+    },
+    /// Compile the markers' JSON files to compact binary files next to them
+    CompileMarkers {
+        #[clap(long, default_value = "assets/markers")]
+        markers_dir: PathBuf,
+        /// Compress the binary files with zstd
+        #[clap(long, action)]
+        zstd: bool,
+    },
+    /// Check the markers' JSON files, only the errors prevent the markers from loading
+    ValidateMarkers {
+        #[clap(long, default_value = "assets/markers")]
+        markers_dir: PathBuf,
+        /// Print the issues as JSON
+        #[clap(long, action)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TilesCommand {
+    /// Build the tiles of every lod from an image of the whole map
+    Build {
+        /// Image of the whole map
+        source: PathBuf,
+        /// A map of `assets/maps.json`, e.g. surface
+        map_type: MapType,
+        /// The tiles are written in its tiles directory
+        #[clap(long, default_value = "assets")]
+        assets_dir: PathBuf,
+        /// JPEG quality, from 1 to 100
+        #[clap(long, default_value_t = 85, value_parser = clap::value_parser!(u8).range(1..=100))]
+        quality: u8,
+        #[clap(long, value_enum, default_value_t = Filter::Lanczos3)]
+        filter: Filter,
+        /// Keep the tiles that are more recent than the source
+        #[clap(long, action)]
+        skip_up_to_date: bool,
+        /// The map's lowest lod by default, it must be one of the map's lods
+        #[clap(long)]
+        min_lod: Option<u32>,
+        /// The map's highest lod by default, the ones above it are ignored
+        #[clap(long)]
+        max_lod: Option<u32>,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Format {
+    Json,
+    Binary,
+}
+
+impl From<Format> for MarkersFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Json => Self::Json,
+            Format::Binary => Self::Binary,
+        }
+    }
+}
+
+impl From<Filter> for FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => Self::Nearest,
+            Filter::Triangle => Self::Triangle,
+            Filter::CatmullRom => Self::CatmullRom,
+            Filter::Gaussian => Self::Gaussian,
+            Filter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+impl Args {
+    /// The formats that aren't given are left to `None`, to be detected
+    fn tile_layouts(&self) -> TileLayouts {
+        let mut tile_layouts = TileLayouts::default();
+        for map_type in MapType::iter() {
+            tile_layouts.get_mut(*map_type).format = None;
+        }
+        for template in &self.tile_template {
+            for map_type in template.map_types() {
+                tile_layouts
+                    .get_mut(*map_type)
+                    .template
+                    .clone_from(&template.value);
+            }
+        }
+        for format in &self.tile_format {
+            for map_type in format.map_types() {
+                tile_layouts.get_mut(*map_type).format = Some(format.value);
+            }
+        }
+        tile_layouts
+    }
+
+    fn markers_source(&self) -> DirMarkerSource {
+        DirMarkerSource {
+            dir: self.markers_dir.clone(),
+            format: self.markers_format.into(),
+        }
+    }
 }
 
 impl From<Args> for totk_map::resources::Options {
     fn from(args: Args) -> Self {
+        let tile_layouts = args.tile_layouts();
+        let markers_source = args.markers_source();
         Self {
             debug_display: args.debug_display,
             canvas: None,
+            tile_budget: totk_map::resources::TileBudget::Megabytes(args.tile_cache_mb),
+            tile_prefetch: totk_map::resources::TilePrefetch {
+                ring: args.tile_prefetch_ring,
+                ..Default::default()
+            },
+            tile_layouts,
+            markers_source: Some(markers_source),
         }
     }
 }
 
-#[allow(clippy::unnecessary_wraps)]
 fn main() -> anyhow::Result<()> {
-    totk_map::run(Args::parse().into());
+    // The maps must be known to parse the arguments, the bundled manifest is used without the
+    // assets
+    let manifest = Path::new("assets/maps.json");
+    if manifest.is_file() {
+        MapRegistry::load(manifest)?.install()?;
+    }
+    let mut args = Args::parse();
+    match args.command.take() {
+        None => totk_map::run(args.into()),
+        Some(Command::Tiles {
+            command:
+                TilesCommand::Build {
+                    source,
+                    map_type,
+                    assets_dir,
+                    quality,
+                    filter,
+                    skip_up_to_date,
+                    min_lod,
+                    max_lod,
+                },
+        }) => {
+            let report = build_pyramid(
+                &source,
+                map_type,
+                &assets_dir,
+                &PyramidOptions {
+                    quality,
+                    filter: filter.into(),
+                    skip_up_to_date,
+                    lods: (min_lod.is_some() || max_lod.is_some()).then(|| {
+                        let lods = &map_type.info().lods;
+                        min_lod.unwrap_or(*lods.start())
+                            ..=max_lod.unwrap_or(u32::MAX).min(*lods.end())
+                    }),
+                },
+                |lod| println!("{map_type} lod {lod} done"),
+            )?;
+            println!(
+                "{} tiles written, {} up to date",
+                report.written, report.skipped
+            );
+        }
+        Some(Command::CheckAssets { json }) => {
+            let markers = block_on(Markers::load(&args.markers_source()))?;
+            let report = AssetsReport::check(Path::new("assets"), &markers, &args.tile_layouts());
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{report}");
+            }
+            if !report.is_ok() {
+                bail!("{} problems found in the assets", report.problems.len());
+            }
+        }
+        Some(Command::CompileMarkers { markers_dir, zstd }) => {
+            let compression = if zstd {
+                Compression::Zstd
+            } else {
+                Compression::None
+            };
+            for file in compile_dir(&markers_dir, compression)? {
+                println!(
+                    "{}: {} bytes, {} bytes as JSON",
+                    file.path.display(),
+                    file.binary_size,
+                    file.json_size
+                );
+            }
+        }
+        Some(Command::ValidateMarkers { markers_dir, json }) => {
+            let validation = MarkersValidation::validate(&MarkersFile::read_all(&markers_dir)?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&validation)?);
+            } else {
+                print!("{validation}");
+            }
+            if validation.has_errors() {
+                bail!("{} errors found in the markers", validation.errors);
+            }
+        }
+    }
     Ok(())
 }
@@ -1,13 +1,23 @@
-use std::ops::RangeBounds;
+use std::ops::RangeInclusive;
 
-use bevy::prelude::*;
+use bevy::{asset::LoadState, math::Rect, prelude::*, utils::HashSet};
 
 use crate::{
     camera::MainCamera,
-    resources::{LoadedTiles, Lod, MapType},
+    coords::WorldPos,
+    markers::MARKERS_Z,
+    resources::{
+        AppState, Lod, MapType, TileBudget, TileCache, TileKey, TileLayouts, TilePrefetch,
+    },
 };
 
-pub struct MapsPlugin;
+#[derive(Default)]
+pub struct MapsPlugin {
+    pub tile_budget: TileBudget,
+    pub tile_prefetch: TilePrefetch,
+    /// The formats left to `None` are detected at startup
+    pub tile_layouts: TileLayouts,
+}
 
 #[derive(Component)]
 pub struct Tile {
@@ -18,46 +28,56 @@ pub struct Tile {
 }
 
 impl Tile {
-    fn is_in_ranges(
-        &self,
-        x_range: &impl RangeBounds<u32>,
-        y_range: &impl RangeBounds<u32>,
-    ) -> bool {
-        x_range.contains(&self.x_idx) && y_range.contains(&self.y_idx)
+    #[must_use]
+    pub fn key(&self) -> TileKey {
+        TileKey {
+            map_type: self.map_type,
+            lod: self.lod,
+            x_idx: self.x_idx,
+            y_idx: self.y_idx,
+        }
     }
 }
 
 impl Plugin for MapsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MapType>()
-            .init_resource::<LoadedTiles>()
-            .add_systems(Startup, maps)
-            .add_systems(Update, load_tiles);
+            .insert_resource(TileCache::new(self.tile_budget))
+            .insert_resource(self.tile_prefetch)
+            .insert_resource(self.tile_layouts.clone())
+            .add_systems(Startup, detect_tile_formats)
+            .add_systems(OnEnter(AppState::Ready), spawn_base_layer)
+            .add_systems(
+                Update,
+                (
+                    spawn_base_layer.run_if(resource_changed::<MapType>()),
+                    load_tiles,
+                    prefetch_tiles,
+                    evict_tiles,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Ready)),
+            );
     }
 }
 
-#[allow(clippy::missing_panics_doc)]
+/// Spawns the tile's hidden sprite, to be recorded in the [`TileCache`]
 pub fn load_tile(
     commands: &mut Commands,
     assets_server: &AssetServer,
-    loaded_tiles: &mut LoadedTiles,
-    map_type: MapType,
-    lod: Lod,
-    x_idx: u32,
-    y_idx: u32,
-) {
-    let path = map_type.tile_path(lod, x_idx, y_idx);
-    if loaded_tiles.tiles().contains(&path) {
-        return;
-    }
-    loaded_tiles.tiles_mut().push(path);
-    let path = loaded_tiles.tiles().last().unwrap().as_path();
+    tile_layouts: &TileLayouts,
+    key: TileKey,
+) -> Entity {
+    let TileKey {
+        map_type,
+        lod,
+        x_idx,
+        y_idx,
+    } = key;
+    let path = tile_layouts.get(map_type).path(map_type, lod, x_idx, y_idx);
+    let tile_px_size = lod.tile_px_size(map_type);
 
-    let tiles_nb = lod.tiles_nb();
-    let tile_px_size = lod.tile_px_size();
-    let translation = tile_px_size / 2.0;
-
-    #[allow(clippy::cast_precision_loss)]
+    // Displayed by `load_tiles` when it's in the viewport, it may only be prefetched
     commands
         .spawn(SpriteBundle {
             texture: assets_server.load(path),
@@ -65,11 +85,8 @@ pub fn load_tile(
                 custom_size: Some(Vec2::new(tile_px_size, tile_px_size)),
                 ..default()
             },
-            transform: Transform::from_xyz(
-                -(tiles_nb as f32 / 2.0 - x_idx as f32) * tile_px_size + translation,
-                (tiles_nb as f32 / 2.0 - y_idx as f32) * tile_px_size - translation,
-                lod * 10.0,
-            ),
+            transform: Transform::from_translation(key.center().extend(tile_z(map_type, lod))),
+            visibility: Visibility::Hidden,
             ..default()
         })
         .insert(Tile {
@@ -77,43 +94,103 @@ pub fn load_tile(
             lod,
             x_idx,
             y_idx,
-        });
+        })
+        .id()
 }
 
+/// The more detailed tiles are drawn over the less detailed ones, all of them under the markers
+#[allow(clippy::cast_precision_loss)]
+#[must_use]
+pub fn tile_z(map_type: MapType, lod: Lod) -> f32 {
+    let lods_nb = map_type.lods().count() as f32;
+    (lod.value() - map_type.min_lod().value()) as f32 * MARKERS_Z / lods_nb
+}
+
+/// The tiles are looked for in the assets directory on desktop, on the web they can't be listed and
+/// the undetected formats are JPEG
+fn detect_tile_formats(mut tile_layouts: ResMut<TileLayouts>) {
+    for map_type in MapType::iter() {
+        let layout = tile_layouts.get_mut(*map_type);
+        if layout.format.is_some() {
+            continue;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let format = layout.detect_format(*map_type, std::path::Path::new("./assets"));
+        #[cfg(target_arch = "wasm32")]
+        let format = None;
+        if let Some(format) = format {
+            info!(target: "tiles", "map={map_type} format={format}");
+        } else {
+            warn!(target: "tiles", "map={map_type} format not detected, using jpg");
+        }
+        layout.format = Some(format.unwrap_or_default());
+    }
+}
+
+/// The map's least detailed tiles are the last resort fallback, they are pinned in the cache when
+/// the map is first displayed
 #[allow(clippy::needless_pass_by_value)]
-fn maps(
+fn spawn_base_layer(
     mut commands: Commands,
     assets_server: Res<AssetServer>,
-    mut loaded_tiles: ResMut<LoadedTiles>,
+    mut tile_cache: ResMut<TileCache>,
+    tile_layouts: Res<TileLayouts>,
     map_type: Res<MapType>,
 ) {
-    let lod = Lod::MIN;
+    let lod = map_type.min_lod();
     let tiles_nb = lod.tiles_nb();
 
     for x_idx in 0..tiles_nb {
         for y_idx in 0..tiles_nb {
-            load_tile(
-                &mut commands,
-                &assets_server,
-                &mut loaded_tiles,
-                *map_type,
+            let key = TileKey {
+                map_type: *map_type,
                 lod,
                 x_idx,
                 y_idx,
-            );
+            };
+            if !tile_cache.contains(key) {
+                let entity = load_tile(&mut commands, &assets_server, &tile_layouts, key);
+                tile_cache.insert_pinned(key, entity);
+            }
         }
     }
 }
 
+/// The indexes of the map's tiles intersecting `area`, in world coordinates, at `lod`
+#[must_use]
+pub fn tile_ranges(
+    map_type: MapType,
+    lod: Lod,
+    area: Rect,
+) -> (RangeInclusive<u32>, RangeInclusive<u32>) {
+    let top_left = WorldPos(Vec2::new(area.min.x, area.max.y)).tile(map_type, lod);
+    let bottom_right = WorldPos(Vec2::new(area.max.x, area.min.y)).tile(map_type, lod);
+    (
+        top_left.x_idx..=bottom_right.x_idx,
+        top_left.y_idx..=bottom_right.y_idx,
+    )
+}
+
+fn viewport(projection: &OrthographicProjection, camera: &Transform) -> Rect {
+    Rect::from_center_size(camera.translation.truncate(), projection.area.size())
+}
+
+/// The closest ancestor of `key` that is loaded, displayed in place of a tile that isn't
+#[must_use]
+pub fn fallback_tile(key: TileKey, is_loaded: impl Fn(TileKey) -> bool) -> Option<TileKey> {
+    std::iter::successors(key.parent(), |key| key.parent()).find(|key| is_loaded(*key))
+}
+
 #[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
 fn load_tiles(
     mut commands: Commands,
     assets_server: Res<AssetServer>,
-    mut loaded_tiles: ResMut<LoadedTiles>,
+    mut tile_cache: ResMut<TileCache>,
+    tile_layouts: Res<TileLayouts>,
     camera: Query<&OrthographicProjection, With<MainCamera>>,
     position: Query<&Transform, With<MainCamera>>,
     lod: Res<Lod>,
-    mut tiles: Query<(&mut Visibility, &Tile)>,
+    mut tiles: Query<(&mut Visibility, &Tile, &Handle<Image>)>,
     map_type: Res<MapType>,
 ) {
     let Ok(projection) = camera.get_single() else {
@@ -123,33 +200,182 @@ fn load_tiles(
         return;
     };
 
-    let xs = lod.index(camera.translation.x + projection.area.min.x)
-        ..=lod.index(camera.translation.x + projection.area.max.x);
-    let ys = lod.index(-camera.translation.y + projection.area.min.y)
-        ..=lod.index(-camera.translation.y + projection.area.max.y);
+    let (xs, ys) = tile_ranges(*map_type, *lod, viewport(projection, camera));
 
-    for (mut tile_visibility, tile) in &mut tiles {
-        // TODO: We should only display the tiles that are contained in the ranges for the inferior lods as well
-        *tile_visibility = if tile.map_type == *map_type
-            && (tile.lod == 0 || tile.lod < *lod || tile.is_in_ranges(&xs, &ys))
-        {
+    tile_cache.next_frame();
+
+    let mut visible_tiles = HashSet::new();
+    for x_idx in xs {
+        for y_idx in ys.clone() {
+            let key = TileKey {
+                map_type: *map_type,
+                lod: *lod,
+                x_idx,
+                y_idx,
+            };
+            if !tile_cache.contains(key) {
+                let entity = load_tile(&mut commands, &assets_server, &tile_layouts, key);
+                tile_cache.insert(key, entity);
+            }
+            visible_tiles.insert(key);
+            // The tiles spawned during this frame aren't in the query yet
+            let is_loaded = |key| {
+                tile_cache
+                    .peek(key)
+                    .and_then(|entity| tiles.get(entity).ok())
+                    .is_some_and(|(_, _, texture)| {
+                        assets_server.get_load_state(texture) == LoadState::Loaded
+                    })
+            };
+            // Until the tile is loaded, or if it failed to, its closest loaded ancestor is
+            // displayed underneath
+            if !is_loaded(key) {
+                visible_tiles.extend(fallback_tile(key, is_loaded));
+            }
+        }
+    }
+
+    for (mut tile_visibility, tile, _) in &mut tiles {
+        let key = tile.key();
+        tile_visibility.set_if_neq(if visible_tiles.contains(&key) {
+            tile_cache.touch(key);
             Visibility::Visible
         } else {
             Visibility::Hidden
-        };
+        });
     }
+}
 
-    for x_idx in xs {
-        for y_idx in ys.clone() {
-            load_tile(
-                &mut commands,
-                &assets_server,
-                &mut loaded_tiles,
-                *map_type,
-                *lod,
+/// The hidden tiles to load ahead, closest to the predicted viewport first. They are around the
+/// visible tiles, along the camera's `velocity`, and at the next lod when `zooming_in`.
+#[must_use]
+pub fn prefetched_tiles(
+    map_type: MapType,
+    lod: Lod,
+    viewport: Rect,
+    velocity: Vec2,
+    zooming_in: bool,
+    prefetch: &TilePrefetch,
+) -> Vec<TileKey> {
+    let keys = |lod: Lod, (xs, ys): (RangeInclusive<u32>, RangeInclusive<u32>)| {
+        xs.flat_map(move |x_idx| {
+            ys.clone().map(move |y_idx| TileKey {
+                map_type,
+                lod,
                 x_idx,
                 y_idx,
-            );
+            })
+        })
+    };
+
+    let (xs, ys) = tile_ranges(map_type, lod, viewport);
+    let last_idx = lod.tiles_nb() - 1;
+    let ring = (
+        xs.start().saturating_sub(prefetch.ring)..=(xs.end() + prefetch.ring).min(last_idx),
+        ys.start().saturating_sub(prefetch.ring)..=(ys.end() + prefetch.ring).min(last_idx),
+    );
+    let offset = velocity * prefetch.look_ahead;
+    let look_ahead = tile_ranges(
+        map_type,
+        lod,
+        Rect::from_center_size(viewport.center() + offset, viewport.size()),
+    );
+    let next_lod = (zooming_in && lod < map_type.max_lod())
+        .then(|| {
+            let next_lod = Lod::new(lod.value() + 1);
+            keys(next_lod, tile_ranges(map_type, next_lod, viewport))
+        })
+        .into_iter()
+        .flatten();
+
+    let visible = keys(lod, (xs, ys)).collect::<HashSet<_>>();
+    let mut prefetched = keys(lod, ring)
+        .chain(keys(lod, look_ahead))
+        .chain(next_lod)
+        .filter(|key| !visible.contains(key))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let predicted_center = viewport.center() + offset;
+    prefetched.sort_by(|key, other_key| {
+        key.center()
+            .distance_squared(predicted_center)
+            .total_cmp(&other_key.center().distance_squared(predicted_center))
+            .then_with(|| key.lod.cmp(&other_key.lod))
+    });
+    prefetched
+}
+
+/// The camera's movement, smoothed over a few frames
+#[derive(Default)]
+struct CameraMotion {
+    previous: Option<(Vec2, f32)>,
+    /// In world units per second
+    velocity: Vec2,
+    /// Variation of the projection's scale per second, negative when zooming in
+    zoom_speed: f32,
+}
+
+const MOTION_SMOOTHING: f32 = 0.2;
+
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
+fn prefetch_tiles(
+    mut commands: Commands,
+    assets_server: Res<AssetServer>,
+    mut tile_cache: ResMut<TileCache>,
+    tile_layouts: Res<TileLayouts>,
+    camera: Query<(&OrthographicProjection, &Transform), With<MainCamera>>,
+    lod: Res<Lod>,
+    map_type: Res<MapType>,
+    prefetch: Res<TilePrefetch>,
+    time: Res<Time>,
+    mut motion: Local<CameraMotion>,
+) {
+    let Ok((projection, camera)) = camera.get_single() else {
+        return;
+    };
+
+    let position = camera.translation.truncate();
+    let delta = time.delta_seconds();
+    if let Some((previous_position, previous_scale)) = motion.previous {
+        if delta > 0.0 {
+            let velocity = (position - previous_position) / delta;
+            let zoom_speed = (projection.scale - previous_scale) / delta;
+            motion.velocity = motion.velocity.lerp(velocity, MOTION_SMOOTHING);
+            motion.zoom_speed += (zoom_speed - motion.zoom_speed) * MOTION_SMOOTHING;
+        }
+    }
+    motion.previous = Some((position, projection.scale));
+
+    // The prefetched tiles only use the budget left, otherwise they would be evicted first, before
+    // they are displayed, and loaded again
+    let max_requested = prefetch
+        .max_per_frame
+        .min(tile_cache.max_tiles().saturating_sub(tile_cache.len()));
+    let mut requested = 0;
+    for key in prefetched_tiles(
+        *map_type,
+        *lod,
+        viewport(projection, camera),
+        motion.velocity,
+        motion.zoom_speed < 0.0,
+        &prefetch,
+    ) {
+        // The tiles are loaded a few per frame so that the visible tiles are loaded first, only the
+        // visible ones are touched to keep the eviction least recently visible first
+        if !tile_cache.contains(key) && requested < max_requested {
+            requested += 1;
+            let entity = load_tile(&mut commands, &assets_server, &tile_layouts, key);
+            tile_cache.insert_prefetched(key, entity);
         }
     }
 }
+
+#[allow(clippy::needless_pass_by_value)]
+fn evict_tiles(mut commands: Commands, mut tile_cache: ResMut<TileCache>) {
+    // Despawning the tiles drops their image handles, which unloads the images
+    for (key, entity) in tile_cache.evict() {
+        debug!(target: "tiles", "evicted={key:?}");
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,132 @@
+//! Compact binary encoding of the markers' files, compiled from their JSON by the
+//! `totk compile-markers` subcommand. It's bincode, optionally compressed with zstd, after a small
+//! header.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    marker_source::{MarkersFileKind, MarkersFormat},
+    resources::MapType,
+    types::{Location, Material},
+};
+
+const MAGIC: &[u8; 4] = b"TKMK";
+/// Bumped when the encoding, or the markers' types, change
+const VERSION: u8 = 1;
+#[cfg(not(target_arch = "wasm32"))]
+const ZSTD_LEVEL: i32 = 19;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Only available on desktop, the compressed files are read on all platforms
+    Zstd,
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd => 1,
+        }
+    }
+}
+
+/// Whether `bytes` are binary markers rather than JSON
+#[must_use]
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+#[allow(clippy::missing_errors_doc)]
+pub fn encode<T: Serialize + ?Sized>(
+    value: &T,
+    compression: Compression,
+) -> anyhow::Result<Vec<u8>> {
+    let payload = bincode::serialize(value)?;
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([VERSION, compression.tag()]);
+    match compression {
+        Compression::None => bytes.extend(payload),
+        #[cfg(not(target_arch = "wasm32"))]
+        Compression::Zstd => bytes.extend(zstd::encode_all(payload.as_slice(), ZSTD_LEVEL)?),
+        #[cfg(target_arch = "wasm32")]
+        Compression::Zstd => bail!("the markers can't be compressed on the web"),
+    }
+    Ok(bytes)
+}
+
+#[allow(clippy::missing_errors_doc)]
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+    let Some(header) = bytes.strip_prefix(MAGIC) else {
+        bail!("not binary markers");
+    };
+    let [version, compression, payload @ ..] = header else {
+        bail!("truncated binary markers");
+    };
+    if *version != VERSION {
+        bail!("binary markers version {version}, expected {VERSION}, they must be compiled again");
+    }
+    let value = match *compression {
+        0 => bincode::deserialize(payload)?,
+        1 => {
+            let decoder = ruzstd::StreamingDecoder::new(payload)
+                .map_err(|err| anyhow!("invalid zstd frame: {err}"))?;
+            bincode::deserialize_from(decoder).context("couldn't decompress the markers")?
+        }
+        compression => bail!("unknown compression {compression}"),
+    };
+    Ok(value)
+}
+
+#[derive(Debug)]
+pub struct CompiledFile {
+    pub path: PathBuf,
+    pub json_size: usize,
+    pub binary_size: usize,
+}
+
+/// Compiles the JSON files of every map in `markers_dir` to binary files next to them
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::missing_errors_doc)]
+pub fn compile_dir(
+    markers_dir: &Path,
+    compression: Compression,
+) -> anyhow::Result<Vec<CompiledFile>> {
+    let mut compiled = Vec::new();
+    for map_type in MapType::iter() {
+        for kind in MarkersFileKind::iter() {
+            let json_path = markers_dir.join(MarkersFormat::Json.path(*map_type, *kind));
+            let json = std::fs::read(&json_path)
+                .with_context(|| format!("couldn't read {}", json_path.display()))?;
+            let parse_error = || format!("couldn't parse {}", json_path.display());
+            // Parsed to the markers' types so that the binary files are exactly what's loaded
+            let binary = match kind {
+                MarkersFileKind::Locations => encode(
+                    &serde_json::from_slice::<Vec<Location>>(&json).with_context(parse_error)?,
+                    compression,
+                )?,
+                MarkersFileKind::Materials => encode(
+                    &serde_json::from_slice::<Vec<Material>>(&json).with_context(parse_error)?,
+                    compression,
+                )?,
+            };
+            let path = markers_dir.join(MarkersFormat::Binary.path(*map_type, *kind));
+            std::fs::write(&path, &binary)
+                .with_context(|| format!("couldn't write {}", path.display()))?;
+            compiled.push(CompiledFile {
+                path,
+                json_size: json.len(),
+                binary_size: binary.len(),
+            });
+        }
+    }
+    Ok(compiled)
+}
@@ -0,0 +1,248 @@
+//! Sources of the markers' files, read by [`Markers::load`](crate::resources::Markers::load).
+
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bevy::{
+    prelude::AssetServer,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{marker_binary, resources::MapType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarkersFileKind {
+    Locations,
+    Materials,
+}
+
+impl MarkersFileKind {
+    #[must_use]
+    pub fn iter() -> &'static [Self] {
+        &[Self::Locations, Self::Materials]
+    }
+}
+
+impl Display for MarkersFileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Locations => write!(f, "locations"),
+            Self::Materials => write!(f, "materials"),
+        }
+    }
+}
+
+/// The encoding of the markers' files, the binary ones are compiled from the JSON ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkersFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl MarkersFormat {
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Binary => "bin",
+        }
+    }
+
+    /// The file's path in the markers directory, from the maps' manifest, with this format's
+    /// extension
+    #[must_use]
+    pub fn path(self, map_type: MapType, kind: MarkersFileKind) -> PathBuf {
+        Path::new(map_type.info().markers_file(kind)).with_extension(self.extension())
+    }
+}
+
+/// Where the markers' files are read from, one locations and one materials file per map, in
+/// either [`MarkersFormat`]
+pub trait MarkerSource: Send + Sync + 'static {
+    fn read(
+        &self,
+        map_type: MapType,
+        kind: MarkersFileKind,
+    ) -> BoxedFuture<'_, anyhow::Result<Vec<u8>>>;
+
+    /// The directory of the JSON files read, which are reloaded when they change on desktop.
+    /// `None` when the files can't be edited, e.g. when they are embedded.
+    fn json_dir(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Reads one of the files from `source` and decodes it, whatever its format
+#[allow(clippy::missing_errors_doc)]
+pub async fn read_markers<T: DeserializeOwned>(
+    source: &dyn MarkerSource,
+    map_type: MapType,
+    kind: MarkersFileKind,
+) -> anyhow::Result<T> {
+    let content = source.read(map_type, kind).await?;
+    if marker_binary::is_binary(&content) {
+        marker_binary::decode(&content)
+    } else {
+        serde_json::from_slice(&content).map_err(Into::into)
+    }
+    .with_context(|| format!("couldn't parse the {map_type} {kind}"))
+}
+
+/// Reads the maps' files listed in the manifest from `dir`, e.g. `{dir}/sky/locations.json`, or
+/// the `.bin` ones
+#[derive(Debug, Clone)]
+pub struct DirMarkerSource {
+    pub dir: PathBuf,
+    pub format: MarkersFormat,
+}
+
+impl Default for DirMarkerSource {
+    fn default() -> Self {
+        Self::new("./assets/markers")
+    }
+}
+
+impl DirMarkerSource {
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            format: MarkersFormat::Json,
+        }
+    }
+
+    #[must_use]
+    pub fn path(&self, map_type: MapType, kind: MarkersFileKind) -> PathBuf {
+        self.dir.join(self.format.path(map_type, kind))
+    }
+}
+
+impl MarkerSource for DirMarkerSource {
+    fn read(
+        &self,
+        map_type: MapType,
+        kind: MarkersFileKind,
+    ) -> BoxedFuture<'_, anyhow::Result<Vec<u8>>> {
+        let path = self.path(map_type, kind);
+        Box::pin(async move {
+            std::fs::read(&path).with_context(|| format!("couldn't read {}", path.display()))
+        })
+    }
+
+    fn json_dir(&self) -> Option<&Path> {
+        (self.format == MarkersFormat::Json).then_some(self.dir.as_path())
+    }
+}
+
+/// Files held in memory, e.g. included in the binary, or generated
+#[derive(Debug, Default, Clone)]
+pub struct EmbeddedMarkerSource {
+    files: HashMap<(MapType, MarkersFileKind), Cow<'static, [u8]>>,
+}
+
+impl EmbeddedMarkerSource {
+    /// The markers of the bundled maps, compiled by `totk compile-markers`, included in the binary
+    #[must_use]
+    pub fn bundled() -> Self {
+        let mut source = Self::default();
+        source
+            .insert(
+                MapType::SKY,
+                MarkersFileKind::Locations,
+                include_bytes!("../assets/markers/sky/locations.bin").as_slice(),
+            )
+            .insert(
+                MapType::SKY,
+                MarkersFileKind::Materials,
+                include_bytes!("../assets/markers/sky/materials.bin").as_slice(),
+            )
+            .insert(
+                MapType::SURFACE,
+                MarkersFileKind::Locations,
+                include_bytes!("../assets/markers/surface/locations.bin").as_slice(),
+            )
+            .insert(
+                MapType::SURFACE,
+                MarkersFileKind::Materials,
+                include_bytes!("../assets/markers/surface/materials.bin").as_slice(),
+            )
+            .insert(
+                MapType::DEPTHS,
+                MarkersFileKind::Locations,
+                include_bytes!("../assets/markers/depths/locations.bin").as_slice(),
+            )
+            .insert(
+                MapType::DEPTHS,
+                MarkersFileKind::Materials,
+                include_bytes!("../assets/markers/depths/materials.bin").as_slice(),
+            );
+        source
+    }
+
+    pub fn insert(
+        &mut self,
+        map_type: MapType,
+        kind: MarkersFileKind,
+        content: impl Into<Cow<'static, [u8]>>,
+    ) -> &mut Self {
+        self.files.insert((map_type, kind), content.into());
+        self
+    }
+}
+
+impl MarkerSource for EmbeddedMarkerSource {
+    fn read(
+        &self,
+        map_type: MapType,
+        kind: MarkersFileKind,
+    ) -> BoxedFuture<'_, anyhow::Result<Vec<u8>>> {
+        let content = self
+            .files
+            .get(&(map_type, kind))
+            .map(|content| content.to_vec())
+            .with_context(|| format!("no {map_type} {kind} embedded"));
+        Box::pin(async move { content })
+    }
+}
+
+/// Reads the files through Bevy's asset IO, from `dir` in the assets, so over HTTP on the web
+#[derive(Clone)]
+pub struct AssetServerMarkerSource {
+    pub asset_server: AssetServer,
+    pub dir: PathBuf,
+    pub format: MarkersFormat,
+}
+
+impl AssetServerMarkerSource {
+    #[must_use]
+    pub fn new(asset_server: AssetServer, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            asset_server,
+            dir: dir.into(),
+            format: MarkersFormat::Json,
+        }
+    }
+}
+
+impl MarkerSource for AssetServerMarkerSource {
+    fn read(
+        &self,
+        map_type: MapType,
+        kind: MarkersFileKind,
+    ) -> BoxedFuture<'_, anyhow::Result<Vec<u8>>> {
+        let path = self.dir.join(self.format.path(map_type, kind));
+        Box::pin(async move {
+            self.asset_server
+                .asset_io()
+                .load_path(Path::new(&path))
+                .await
+                .with_context(|| format!("couldn't load {}", path.display()))
+        })
+    }
+}
@@ -1,226 +1,807 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
-use bevy_mod_raycast::RaycastMesh;
+use std::{f32::consts::SQRT_2, sync::Arc};
+
+use futures_lite::future::{self, block_on};
+
+use bevy::{
+    asset::LoadState,
+    ecs::query::ReadOnlyWorldQuery,
+    input::common_conditions::input_just_pressed,
+    math::Rect,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
+    window::PrimaryWindow,
+};
 
 use crate::{
-    camera::MainCamera,
-    picking::RaycastSet,
-    resources::{DisplayedMarkers, FocusedMarkers, Lod, MapType, Markers, SpawnedMarkers},
+    camera::{MainCamera, MapClicked},
+    clusters::{cluster_lods, grid_clusters, spawn_cluster, MarkerCluster},
+    coords::{GameCoord, WorldPos},
+    marker_source::{read_markers, MarkerSource, MarkersFileKind},
+    pins::{PinSprite, PINS_CATEGORY},
+    resources::{
+        AppState, CompletedMarkers, DisplayedMarkers, FocusedMarkers, Lod, MapType, MarkerDetails,
+        Markers, MarkersProgress, SelectedMarker, SpawnedMarkers,
+    },
+    ruler::ruler_is_active,
+    spatial::{MapMarkersIndex, MarkersIndex, QuadTree},
+    storage,
+    types::{Location, LocationLayer, Material},
+    ui::egui_is_hovered,
 };
 
-const LOCATION_ICON_PATH: &str = "icons/mainquest.png";
-const MATERIAL_ICON_PATH: &str = "icons/star.png";
+pub const LOCATION_ICON_PATH: &str = "icons/mainquest.png";
+pub const MATERIAL_ICON_PATH: &str = "icons/star.png";
+const PATH_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.7);
+const FOCUSED_PATH_COLOR: Color = Color::rgb(1.0, 0.85, 0.0);
+const COMPLETED_MARKERS_STORAGE_KEY: &str = "completed";
+/// Of the completed markers whose icon has no `_r` "done" variant
+const COMPLETED_DIMMED_COLOR: Color = Color::rgba(0.5, 0.5, 0.5, 0.6);
+/// Scale of the icons' images, multiplied by the markers' scale
+pub const ICON_SCALE: f32 = 0.1;
+/// The markers are drawn over the tiles, whose z are below it
+pub const MARKERS_Z: f32 = 100.0;
+/// Half size of the markers' clickable area, multiplied by the markers' scale
+const MARKER_HALF_SIZE: f32 = 1.0;
+/// Half size of the largest icons, multiplied by the markers' scale
+const ICON_MAX_HALF_SIZE: f32 = 4.0;
 
-pub struct MarkersPlugin;
+pub struct MarkersPlugin {
+    pub source: Arc<dyn MarkerSource>,
+}
+
+impl MarkersPlugin {
+    pub fn new(source: impl MarkerSource) -> Self {
+        Self {
+            source: Arc::new(source),
+        }
+    }
+}
+
+/// The assets' markers directory on desktop, the markers included in the binary on the web
+impl Default for MarkersPlugin {
+    fn default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let source = crate::marker_source::DirMarkerSource::default();
+        #[cfg(target_arch = "wasm32")]
+        let source = crate::marker_source::EmbeddedMarkerSource::bundled();
+        Self::new(source)
+    }
+}
 
 impl Plugin for MarkersPlugin {
     fn build(&self, app: &mut App) {
-        let markers = Markers::load().expect("markers to load");
-        app.insert_resource(markers)
+        let completed_markers = storage::load(COMPLETED_MARKERS_STORAGE_KEY)
+            .unwrap_or_else(|err| {
+                error!("couldn't load the completed markers: {err:#}");
+                None
+            })
+            .unwrap_or_default();
+        app.add_state::<AppState>()
+            .insert_resource(MarkerSourceRes(self.source.clone()))
+            .init_resource::<Markers>()
+            .init_resource::<MarkersProgress>()
+            .insert_resource::<CompletedMarkers>(completed_markers)
             .init_resource::<SpawnedMarkers>()
             .init_resource::<DisplayedMarkers>()
             .init_resource::<FocusedMarkers>()
-            .add_systems(Startup, draw_markers)
+            .init_resource::<SelectedMarker>()
+            .init_resource::<MarkersIndex>()
+            .add_systems(Startup, start_loading_markers)
+            .add_systems(
+                Update,
+                poll_loading_markers.run_if(in_state(AppState::Loading)),
+            )
             .add_systems(
                 Update,
-                (change_markers_visibility, focus_markers, update_scale),
+                (
+                    draw_markers.run_if(
+                        resource_changed::<MapType>().or_else(resource_changed::<Markers>()),
+                    ),
+                    change_markers_visibility,
+                    focus_markers.run_if(not(egui_is_hovered)),
+                    update_scale,
+                    adjust_markers_positions,
+                    draw_markers_paths,
+                    select_marker.run_if(not(ruler_is_active)),
+                    deselect_marker.run_if(input_just_pressed(KeyCode::Escape)),
+                    update_completed_markers_icons,
+                    storage::save_resource::<CompletedMarkers>(COMPLETED_MARKERS_STORAGE_KEY)
+                        .run_if(resource_changed::<CompletedMarkers>()),
+                )
+                    .run_if(in_state(AppState::Ready)),
             );
     }
 }
 
+#[derive(Resource)]
+struct MarkerSourceRes(Arc<dyn MarkerSource>);
+
+enum MarkersFileContent {
+    Locations(Vec<Location>),
+    Materials(Vec<Material>),
+}
+
+/// The markers' files read and parsed on the task pool, each file in its own task
+#[derive(Resource)]
+struct MarkersLoading {
+    tasks: Vec<(
+        MapType,
+        MarkersFileKind,
+        Task<anyhow::Result<MarkersFileContent>>,
+    )>,
+    markers: Markers,
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn start_loading_markers(mut commands: Commands, source: Res<MarkerSourceRes>) {
+    let task_pool = AsyncComputeTaskPool::get();
+    let mut tasks = Vec::new();
+    for map_type in MapType::iter().iter().copied() {
+        for kind in MarkersFileKind::iter().iter().copied() {
+            let source = source.0.clone();
+            let task = task_pool.spawn(async move {
+                Ok(match kind {
+                    MarkersFileKind::Locations => {
+                        MarkersFileContent::Locations(read_markers(&*source, map_type, kind).await?)
+                    }
+                    MarkersFileKind::Materials => {
+                        MarkersFileContent::Materials(read_markers(&*source, map_type, kind).await?)
+                    }
+                })
+            });
+            tasks.push((map_type, kind, task));
+        }
+    }
+    commands.insert_resource(MarkersLoading {
+        tasks,
+        markers: Markers::default(),
+    });
+}
+
+/// Moves the parsed files into the [`Markers`] once they are all loaded, or fails if one of them
+/// couldn't be
+fn poll_loading_markers(
+    mut commands: Commands,
+    mut loading: ResMut<MarkersLoading>,
+    mut progress: ResMut<MarkersProgress>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let loading = &mut *loading;
+    let mut pending = Vec::new();
+    for (map_type, kind, mut task) in loading.tasks.drain(..) {
+        let Some(result) = block_on(future::poll_once(&mut task)) else {
+            pending.push((map_type, kind, task));
+            continue;
+        };
+        match result {
+            Ok(MarkersFileContent::Locations(locations)) => {
+                *loading.markers.locations_mut(map_type) = locations;
+            }
+            Ok(MarkersFileContent::Materials(materials)) => {
+                *loading.markers.materials_mut(map_type) = materials;
+            }
+            Err(err) => {
+                error!("couldn't load the {map_type} {kind}: {err:#}");
+                progress.errors.push(format!("{err:#}"));
+                continue;
+            }
+        }
+        info!(target: "markers", "map={map_type} {kind} loaded");
+        progress.file_loaded(map_type);
+    }
+    loading.tasks = pending;
+
+    if !loading.tasks.is_empty() {
+        return;
+    }
+    if progress.errors.is_empty() {
+        commands.insert_resource(std::mem::take(&mut loading.markers));
+        next_state.set(AppState::Ready);
+    } else {
+        next_state.set(AppState::Failed);
+    }
+    commands.remove_resource::<MarkersLoading>();
+}
+
 #[derive(Component)]
 pub struct MarkerSprite {
+    pub map_type: MapType,
     pub name: String,
     pub layer_name: Option<String>,
+    /// Only location markers have an id
+    pub id: Option<String>,
+    pub coords: GameCoord,
     pub min_lod: u32,
     pub max_lod: u32,
 }
 
-impl MarkerSprite {
-    fn new(name: String, layer_name: Option<String>, min_lod: u32, max_lod: u32) -> Self {
+/// Icons of the markers that can be completed
+#[derive(Clone, Component)]
+pub struct MarkerIcon {
+    default: Handle<Image>,
+    /// `None` when the icon isn't a PNG, the default icon is dimmed if the `_r` variant failed to
+    /// load
+    completed: Option<Handle<Image>>,
+}
+
+impl MarkerIcon {
+    fn new(assets_server: &AssetServer, icon_path: &str) -> Self {
+        let completed =
+            completed_icon_path(icon_path).map(|completed| assets_server.load(completed));
         Self {
-            name,
-            layer_name,
-            min_lod,
-            max_lod,
+            default: assets_server.load(icon_path),
+            completed,
         }
     }
 }
 
-#[allow(clippy::too_many_arguments)]
+/// The icon of the layer's markers, relative to the assets directory
+#[must_use]
+pub fn location_icon_path(layer: &LocationLayer) -> String {
+    layer.icon.as_ref().map_or_else(
+        || LOCATION_ICON_PATH.to_string(),
+        |icon| format!("icons/{}", icon.url),
+    )
+}
+
+/// The `_r` variant of the PNG icon displayed once the marker is completed, it may not exist
+#[must_use]
+pub fn completed_icon_path(icon_path: &str) -> Option<String> {
+    icon_path
+        .strip_suffix(".png")
+        .map(|stem| format!("{stem}_r.png"))
+}
+
+/// The marker's path, in world coordinates
+#[derive(Component)]
+pub struct MarkerPath(Vec<Vec2>);
+
+/// Per-lod positions of the markers that are moved apart at some lods, in world coordinates
+#[derive(Component)]
+pub struct ZoomAdjustedPositions {
+    default: Vec2,
+    by_lod: HashMap<u32, Vec2>,
+}
+
+impl ZoomAdjustedPositions {
+    fn pos(&self, lod: Lod) -> Vec2 {
+        self.by_lod
+            .get(&lod.value())
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl MarkerSprite {
+    /// From the marker's category, lod range and completion, regardless of the viewport
+    fn is_displayed(
+        &self,
+        displayed_markers: &DisplayedMarkers,
+        completed_markers: &CompletedMarkers,
+        lod: Lod,
+    ) -> bool {
+        let is_hidden_completed = displayed_markers.hide_completed
+            && self
+                .id
+                .as_ref()
+                .is_some_and(|id| completed_markers.is_completed(id));
+        !is_hidden_completed
+            && displayed_markers.is_displayed(self.map_type, &self.name)
+            && lod >= self.min_lod
+            && lod <= self.max_lod
+    }
+
+    fn details(&self, markers: &Markers) -> MarkerDetails {
+        let location_marker = self
+            .id
+            .as_ref()
+            .and_then(|id| markers.location_marker(self.map_type, id));
+        MarkerDetails {
+            map_type: self.map_type,
+            category: self.name.clone(),
+            name: self.layer_name.clone(),
+            id: self.id.clone(),
+            coords: self.coords,
+            source: location_marker.and_then(|(location, _)| location.source.clone()),
+            link: location_marker.and_then(|(location, layer_marker)| {
+                layer_marker.link.clone().or_else(|| location.link.clone())
+            }),
+        }
+    }
+}
+
+/// Every marker is a single sprite entity, the sprites sharing an icon share its texture handle
+/// and are drawn in the same batches
 pub fn spawn_markers_for_map(
     commands: &mut Commands,
     assets_server: &AssetServer,
-    meshes: &mut Assets<Mesh>,
-    materials: &mut Assets<ColorMaterial>,
     markers: &Markers,
     displayed_markers: &mut DisplayedMarkers,
     map_type: MapType,
     spawned_markers: &mut SpawnedMarkers,
+    markers_index: &mut MarkersIndex,
 ) {
     if spawned_markers.is_spawned(map_type) {
         return;
     }
 
-    displayed_markers.markers_mut().clear();
+    // Locations and pins are displayed the first time a map is entered, materials are hidden
+    let locations = markers
+        .locations(map_type)
+        .iter()
+        .map(|location| location.name.clone())
+        .chain([PINS_CATEGORY.to_string()]);
+    displayed_markers.add_missing_from(map_type, locations);
+
+    markers_index.insert(
+        map_type,
+        spawn_map_markers(commands, assets_server, markers, map_type),
+    );
+    spawned_markers.mark_spawned(map_type);
+}
+
+/// Spawns the markers of a map, and the clusters replacing them at the low lods, without changing
+/// the displayed categories
+#[allow(clippy::too_many_lines)]
+pub fn spawn_map_markers(
+    commands: &mut Commands,
+    assets_server: &AssetServer,
+    markers: &Markers,
+    map_type: MapType,
+) -> MapMarkersIndex {
+    let mut indexed_markers = Vec::new();
+    // The largest distance between a marker's indexed position and its zoom adjusted ones
+    let mut margin: f32 = 0.0;
+
     for location in markers.locations(map_type) {
-        displayed_markers
-            .markers_mut()
-            .insert(location.name.clone());
         for layer in &location.layers {
-            let icon_path = layer.icon.as_ref().map_or_else(
-                || LOCATION_ICON_PATH.to_string(),
-                |icon| format!("icons/{}", icon.url),
-            );
+            let icon_path = location_icon_path(layer);
+            let marker_icon = MarkerIcon::new(assets_server, &icon_path);
 
             for layer_marker in &layer.markers {
-                commands
-                    .spawn(MaterialMesh2dBundle {
-                        mesh: meshes
-                            .add(Mesh::from(shape::Quad::new(Vec2::splat(2.0))))
-                            .into(),
-                        material: materials
-                            .add(ColorMaterial::from(Color::rgba(0.0, 0.0, 0.0, 0.0))),
-                        transform: Transform::from_xyz(
-                            layer_marker.pos.y,
-                            layer_marker.pos.x,
-                            100.0,
-                        ),
-                        ..default()
-                    })
-                    .with_children(|commands| {
-                        commands.spawn(SpriteBundle {
-                            texture: assets_server.load(&icon_path),
-                            transform: Transform::from_scale(Vec3::splat(0.1)),
-                            ..default()
-                        });
-                    })
-                    .insert(MarkerSprite::new(
-                        location.name.to_string(),
-                        layer_marker.name.clone(),
-                        layer.min_lod,
-                        layer.max_lod,
-                    ))
-                    .insert(RaycastMesh::<RaycastSet>::default());
+                let coords = GameCoord::from_json(layer_marker.pos, layer_marker.elv);
+                let world_pos = coords.to_world().0;
+                let mut marker = commands.spawn((
+                    marker_sprite_bundle(marker_icon.default.clone(), world_pos),
+                    MarkerSprite {
+                        map_type,
+                        name: location.name.clone(),
+                        layer_name: layer_marker.name.clone(),
+                        id: Some(layer_marker.id.clone()),
+                        coords,
+                        min_lod: layer.min_lod,
+                        max_lod: layer.max_lod,
+                    },
+                    marker_icon.clone(),
+                ));
+                indexed_markers.push((world_pos, marker.id()));
+                if !layer_marker.zoom_adjusted_pos.is_empty() {
+                    let by_lod = layer_marker
+                        .zoom_adjusted_pos
+                        .iter()
+                        .map(|(lod, pos)| (*lod, WorldPos::from_json(*pos).0))
+                        .collect::<HashMap<_, _>>();
+                    margin = by_lod
+                        .values()
+                        .map(|pos| pos.distance(world_pos))
+                        .fold(margin, f32::max);
+                    marker.insert(ZoomAdjustedPositions {
+                        default: world_pos,
+                        by_lod,
+                    });
+                }
+                if !layer_marker.path.is_empty() {
+                    marker.insert(MarkerPath(
+                        layer_marker
+                            .path
+                            .iter()
+                            .map(|pos| WorldPos::from_json(*pos).0)
+                            .collect(),
+                    ));
+                }
             }
         }
     }
 
+    let material_icon = assets_server.load(MATERIAL_ICON_PATH);
     for material in markers.materials(map_type) {
-        for pos in &material.pos {
-            commands
-                .spawn(MaterialMesh2dBundle {
-                    mesh: meshes
-                        .add(Mesh::from(shape::Quad::new(Vec2::splat(2.0))))
-                        .into(),
-                    material: materials.add(ColorMaterial::from(Color::rgba(0.0, 0.0, 0.0, 0.0))),
-                    transform: Transform::from_xyz(pos.y, pos.x, 100.0),
-                    ..default()
-                })
-                .with_children(|commands| {
-                    commands.spawn(SpriteBundle {
-                        texture: assets_server.load(MATERIAL_ICON_PATH),
-                        transform: Transform::from_scale(Vec3::splat(0.1)),
-                        ..default()
-                    });
-                })
-                .insert(MarkerSprite::new(
-                    material.name.to_string(),
-                    None,
-                    Lod::MIN_VALUE,
-                    Lod::MAX_VALUE,
+        let coords = material
+            .pos
+            .iter()
+            .map(|pos| GameCoord::from_material_pos(*pos))
+            .collect::<Vec<_>>();
+        let world_positions = coords
+            .iter()
+            .map(|coords| coords.to_world().0)
+            .collect::<Vec<_>>();
+        // The highest lod at which each material is in a cluster, the lower lods' cells contain
+        // the higher ones' so they are clustered at these lods too
+        let mut clustered_max_lods = vec![None; world_positions.len()];
+        for lod in cluster_lods(map_type) {
+            for cluster in grid_clusters(&world_positions, map_type, Lod::new(lod)) {
+                let entity = spawn_cluster(
+                    commands,
+                    material_icon.clone(),
+                    map_type,
+                    &material.name,
+                    lod,
+                    &cluster,
+                );
+                indexed_markers.push((cluster.center, entity));
+                for member in cluster.members {
+                    clustered_max_lods[member] = Some(lod);
+                }
+            }
+        }
+
+        for ((coords, world_pos), clustered_max_lod) in coords
+            .into_iter()
+            .zip(world_positions)
+            .zip(clustered_max_lods)
+        {
+            let marker = commands
+                .spawn((
+                    marker_sprite_bundle(material_icon.clone(), world_pos),
+                    MarkerSprite {
+                        map_type,
+                        name: material.name.clone(),
+                        layer_name: None,
+                        id: None,
+                        coords,
+                        min_lod: clustered_max_lod.map_or(Lod::MIN_VALUE, |lod| lod + 1),
+                        max_lod: u32::MAX,
+                    },
                 ))
-                .insert(RaycastMesh::<RaycastSet>::default());
+                .id();
+            indexed_markers.push((world_pos, marker));
         }
     }
 
-    spawned_markers.mark_spawned(map_type);
+    MapMarkersIndex {
+        quad_tree: indexed_markers.into_iter().collect::<QuadTree<_>>(),
+        margin,
+    }
+}
+
+/// Hidden until the visibility system finds it in the viewport
+fn marker_sprite_bundle(texture: Handle<Image>, world_pos: Vec2) -> SpriteBundle {
+    SpriteBundle {
+        texture,
+        transform: Transform::from_translation(world_pos.extend(MARKERS_Z)),
+        visibility: Visibility::Hidden,
+        ..default()
+    }
 }
 
 #[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
 fn draw_markers(
     mut commands: Commands,
     assets_server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
     markers: Res<Markers>,
     mut displayed_markers: ResMut<DisplayedMarkers>,
     map_type: Res<MapType>,
     mut spawned_markers: ResMut<SpawnedMarkers>,
+    mut markers_index: ResMut<MarkersIndex>,
 ) {
     spawn_markers_for_map(
         &mut commands,
         &assets_server,
-        &mut meshes,
-        &mut materials,
         &markers,
         &mut displayed_markers,
         *map_type,
         &mut spawned_markers,
+        &mut markers_index,
     );
 }
 
-#[allow(clippy::needless_pass_by_value)]
+/// Whether the file is a png image, from its extension
+#[must_use]
+pub fn is_png(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+}
+
+/// The markers and the clusters replacing them at the low lods are culled and hovered alike
+type MarkerOrCluster = AnyOf<(&'static MarkerSprite, &'static MarkerCluster)>;
+
+/// The markers' scale for a camera's projection scale, so that they keep a readable size
+#[must_use]
+pub fn marker_scale(projection_scale: f32) -> f32 {
+    (10.0 * projection_scale).clamp(2.0, 200.0)
+}
+
+/// Only the markers of the current map found around the viewport in the index are visited, the
+/// ones that were visible in the previous frame are hidden if they are not anymore.
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
 fn change_markers_visibility(
     displayed_markers: Res<DisplayedMarkers>,
+    completed_markers: Res<CompletedMarkers>,
     lod: Res<Lod>,
-    mut marker_sprites: Query<(&mut Visibility, &MarkerSprite)>,
+    map_type: Res<MapType>,
+    markers_index: Res<MarkersIndex>,
+    camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut marker_sprites: Query<
+        (&mut Visibility, &mut Transform, MarkerOrCluster),
+        Without<MainCamera>,
+    >,
+    mut visible_markers: Local<HashSet<Entity>>,
 ) {
-    for (mut marker_sprite_visibility, marker_sprite) in &mut marker_sprites {
-        *marker_sprite_visibility = if displayed_markers.markers().contains(&marker_sprite.name)
-            && *lod >= marker_sprite.min_lod
-            && *lod <= marker_sprite.max_lod
-        {
-            Visibility::Visible
-        } else {
-            Visibility::Hidden
-        };
+    let Ok((camera_transform, projection)) = camera.get_single() else {
+        return;
+    };
+    let scale = marker_scale(projection.scale);
+
+    let mut newly_visible_markers = HashSet::new();
+    if let Some(index) = markers_index.get(*map_type) {
+        let viewport = Rect {
+            min: projection.area.min + camera_transform.translation.truncate(),
+            max: projection.area.max + camera_transform.translation.truncate(),
+        }
+        .inset(index.margin + ICON_MAX_HALF_SIZE * scale);
+        for entity in index.quad_tree.query_rect(viewport) {
+            let Ok((mut visibility, mut transform, marker)) = marker_sprites.get_mut(entity) else {
+                continue;
+            };
+            let is_visible = match marker {
+                (Some(marker_sprite), _) => {
+                    marker_sprite.is_displayed(&displayed_markers, &completed_markers, *lod)
+                }
+                (None, Some(cluster)) => {
+                    displayed_markers.is_displayed(cluster.map_type, &cluster.name)
+                        && *lod == cluster.lod
+                }
+                (None, None) => false,
+            };
+            if !is_visible {
+                continue;
+            }
+            visibility.set_if_neq(Visibility::Visible);
+            let icon_scale = Vec3::splat(scale * ICON_SCALE);
+            if transform.scale != icon_scale {
+                transform.scale = icon_scale;
+            }
+            newly_visible_markers.insert(entity);
+        }
+    }
+
+    for entity in visible_markers.difference(&newly_visible_markers) {
+        if let Ok((mut visibility, _, _)) = marker_sprites.get_mut(*entity) {
+            *visibility = Visibility::Hidden;
+        }
     }
+    *visible_markers = newly_visible_markers;
 }
 
+/// The markers under the cursor, closest first
 #[allow(clippy::needless_pass_by_value)]
 fn focus_markers(
     mut focused_markers: ResMut<FocusedMarkers>,
-    query: Query<(&RaycastMesh<RaycastSet>, &MarkerSprite)>,
+    map_type: Res<MapType>,
+    markers_index: Res<MarkersIndex>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    marker_sprites: Query<(&Visibility, &Transform, MarkerOrCluster)>,
 ) {
-    *focused_markers = FocusedMarkers::default();
-    for (mesh, marker_sprite) in &query {
-        let mut name: String = marker_sprite.name.clone();
-        if let Some(layer_marker_name) = &marker_sprite.layer_name {
-            if name != layer_marker_name.as_str() {
-                name.push_str(" - ");
-                name.push_str(layer_marker_name);
+    focused_markers.markers_mut().clear();
+    let (Ok(window), Ok((camera, camera_transform, projection)), Some(index)) = (
+        primary_window.get_single(),
+        camera.get_single(),
+        markers_index.get(*map_type),
+    ) else {
+        return;
+    };
+    let Some(cursor_position) = window
+        .cursor_position()
+        .and_then(|cursor_position| camera.viewport_to_world_2d(camera_transform, cursor_position))
+    else {
+        return;
+    };
+
+    let half_size = MARKER_HALF_SIZE * marker_scale(projection.scale);
+    let search_area =
+        Rect::from_center_half_size(cursor_position, Vec2::splat(half_size + index.margin));
+    let mut hovered_markers = index
+        .quad_tree
+        .query_rect(search_area)
+        .into_iter()
+        .filter_map(|entity| {
+            let (visibility, transform, marker) = marker_sprites.get(entity).ok()?;
+            let offset = transform.translation.truncate() - cursor_position;
+            (*visibility == Visibility::Visible && offset.abs().max_element() <= half_size)
+                .then_some((entity, offset.length(), marker))
+        })
+        .collect::<Vec<_>>();
+    hovered_markers
+        .sort_by(|(_, distance, _), (_, other_distance, _)| distance.total_cmp(other_distance));
+
+    for (entity, distance, marker) in hovered_markers {
+        let name = match marker {
+            (Some(marker_sprite), _) => {
+                let mut name: String = marker_sprite.name.clone();
+                if let Some(layer_marker_name) = &marker_sprite.layer_name {
+                    if name != layer_marker_name.as_str() {
+                        name.push_str(" - ");
+                        name.push_str(layer_marker_name);
+                    }
+                }
+                name
             }
+            (None, Some(cluster)) => format!("{} ({})", cluster.name, cluster.count),
+            (None, None) => continue,
+        };
+        debug!(target: "hover", "name={name} distance={distance}");
+        focused_markers.markers_mut().push((entity, name));
+    }
+}
+
+/// The visible marker or cluster whose clickable area contains `pos`, in world coordinates, the
+/// closest one if there are several. The candidates are the index's entities found in `markers`.
+#[must_use]
+pub fn marker_at<F: ReadOnlyWorldQuery>(
+    markers_index: &MarkersIndex,
+    map_type: MapType,
+    pos: Vec2,
+    projection_scale: f32,
+    markers: &Query<(&Visibility, &Transform), F>,
+) -> Option<Entity> {
+    let half_size = MARKER_HALF_SIZE * marker_scale(projection_scale);
+    // The clickable areas are squares, so the farthest markers are in the corners
+    markers_index.nearest(map_type, pos, half_size * SQRT_2, |entity| {
+        let (visibility, transform) = markers.get(entity).ok()?;
+        let marker_pos = transform.translation.truncate();
+        (*visibility == Visibility::Visible && (marker_pos - pos).abs().max_element() <= half_size)
+            .then_some(marker_pos)
+    })
+}
+
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
+fn select_marker(
+    mut map_clicked: EventReader<MapClicked>,
+    map_type: Res<MapType>,
+    markers_index: Res<MarkersIndex>,
+    markers: Res<Markers>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+    clickable_markers: Query<(&Visibility, &Transform)>,
+    marker_sprites: Query<&MarkerSprite>,
+    mut selected_marker: ResMut<SelectedMarker>,
+) {
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+    for map_clicked in &mut map_clicked {
+        if map_clicked.button != MouseButton::Left {
+            continue;
         }
-        for (_, intersection) in mesh.intersections() {
-            debug!(
-                target: "intersection",
-                "name={} distance={:?} position={:?}",
-                name,
-                intersection.distance(),
-                intersection.position(),
-            );
-            focused_markers.markers_mut().push(name.clone());
-        }
+        // A click on a cluster zooms in on it instead
+        let Some(marker_sprite) = marker_at(
+            &markers_index,
+            *map_type,
+            map_clicked.world_position,
+            projection.scale,
+            &clickable_markers,
+        )
+        .and_then(|entity| marker_sprites.get(entity).ok()) else {
+            continue;
+        };
+        selected_marker.select(marker_sprite.details(&markers));
     }
 }
 
+fn deselect_marker(mut selected_marker: ResMut<SelectedMarker>) {
+    selected_marker.clear();
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn update_scale(
-    camera: Query<&OrthographicProjection, With<MainCamera>>,
-    mut marker_sprites: Query<&mut Transform, With<MarkerSprite>>,
+    camera: Query<Ref<OrthographicProjection>, With<MainCamera>>,
+    added_sprites: Query<(), Added<PinSprite>>,
+    mut sprites: Query<&mut Transform, With<PinSprite>>,
 ) {
     let Ok(projection) = camera.get_single() else {
         return;
     };
+    // Newly spawned sprites must be scaled too, even if the camera didn't zoom
+    if !projection.is_changed() && added_sprites.is_empty() {
+        return;
+    }
     debug!(target: "camera", "scale={}", projection.scale);
-    let Some(marker_sprite) = marker_sprites.iter().last() else {
+    // The markers are scaled along with their visibility, only the few pins are left
+    let scale = marker_scale(projection.scale);
+    for mut transform in &mut sprites {
+        transform.scale = Vec3::splat(scale);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn adjust_markers_positions(
+    lod: Res<Lod>,
+    added_markers: Query<(), Added<ZoomAdjustedPositions>>,
+    mut marker_sprites: Query<(&mut Transform, &ZoomAdjustedPositions)>,
+) {
+    // Newly spawned markers must be moved too, as they are spawned at their default position
+    if !lod.is_changed() && added_markers.is_empty() {
         return;
-    };
-    let marker_scale = (10.0 * projection.scale).max(2.0).min(200.0);
-    if (marker_scale - marker_sprite.scale.x).abs() < f32::EPSILON {
+    }
+    for (mut transform, positions) in &mut marker_sprites {
+        let pos = positions.pos(*lod);
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn update_completed_markers_icons(
+    assets_server: Res<AssetServer>,
+    completed_markers: Res<CompletedMarkers>,
+    added_icons: Query<&MarkerIcon, Added<MarkerIcon>>,
+    mut marker_sprites: Query<(&MarkerSprite, &MarkerIcon, &mut Handle<Image>, &mut Sprite)>,
+    mut loading_icons: Local<HashSet<Handle<Image>>>,
+) {
+    // The missing `_r` variants are only known once they failed to load
+    loading_icons.extend(added_icons.iter().filter_map(|icon| icon.completed.clone()));
+    let mut failed_icons = false;
+    loading_icons.retain(|icon| match assets_server.get_load_state(icon) {
+        LoadState::NotLoaded | LoadState::Loading => true,
+        LoadState::Failed => {
+            failed_icons = true;
+            false
+        }
+        LoadState::Loaded | LoadState::Unloaded => false,
+    });
+    if !completed_markers.is_changed() && added_icons.is_empty() && !failed_icons {
         return;
     }
-    for mut marker_sprite in &mut marker_sprites {
-        marker_sprite.scale = Vec3::splat(marker_scale);
+    for (marker_sprite, marker_icon, mut icon_texture, mut icon_sprite) in &mut marker_sprites {
+        let is_completed = marker_sprite
+            .id
+            .as_ref()
+            .is_some_and(|id| completed_markers.is_completed(id));
+        let completed = marker_icon
+            .completed
+            .as_ref()
+            .filter(|completed| assets_server.get_load_state(*completed) != LoadState::Failed);
+        let (texture, color) = match (is_completed, completed) {
+            (true, Some(completed)) => (completed, Color::WHITE),
+            (true, None) => (&marker_icon.default, COMPLETED_DIMMED_COLOR),
+            (false, _) => (&marker_icon.default, Color::WHITE),
+        };
+        if *icon_texture != *texture {
+            *icon_texture = texture.clone();
+        }
+        if icon_sprite.color != color {
+            icon_sprite.color = color;
+        }
+    }
+}
+
+/// The paths are drawn even if their marker is out of the viewport, as they can cross it
+#[allow(clippy::needless_pass_by_value)]
+fn draw_markers_paths(
+    mut gizmos: Gizmos,
+    displayed_markers: Res<DisplayedMarkers>,
+    completed_markers: Res<CompletedMarkers>,
+    lod: Res<Lod>,
+    map_type: Res<MapType>,
+    focused_markers: Res<FocusedMarkers>,
+    marker_paths: Query<(Entity, &MarkerSprite, &MarkerPath)>,
+) {
+    for (entity, marker_sprite, marker_path) in &marker_paths {
+        if marker_sprite.map_type != *map_type
+            || !marker_sprite.is_displayed(&displayed_markers, &completed_markers, *lod)
+        {
+            continue;
+        }
+        let is_focused = focused_markers
+            .markers()
+            .iter()
+            .any(|(focused_entity, _)| *focused_entity == entity);
+        let color = if is_focused {
+            FOCUSED_PATH_COLOR
+        } else {
+            PATH_COLOR
+        };
+        gizmos.linestrip_2d(marker_path.0.iter().copied(), color);
     }
 }
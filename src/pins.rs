@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::MapClicked,
+    markers::{is_png, ICON_SCALE},
+    resources::{AppState, DisplayedMarkers, MapType, Markers, PinEditor, PinIcons, Pins},
+    storage,
+};
+
+/// Filter category of the pins, toggled like the other markers categories
+pub const PINS_CATEGORY: &str = "Custom pins";
+const PINS_STORAGE_KEY: &str = "pins";
+const DEFAULT_PIN_ICON: &str = "flag.png";
+/// Always available, on top of the icons used by the locations
+const BASE_PIN_ICONS: &[&str] = &[
+    "flag.png",
+    "flag2.png",
+    "mainquest.png",
+    "objective.png",
+    "star.png",
+];
+
+pub struct PinsPlugin;
+
+impl Plugin for PinsPlugin {
+    fn build(&self, app: &mut App) {
+        let pins = storage::load(PINS_STORAGE_KEY)
+            .unwrap_or_else(|err| {
+                error!("couldn't load the pins: {err:#}");
+                None
+            })
+            .unwrap_or_default();
+        app.insert_resource::<Pins>(pins)
+            .init_resource::<PinEditor>()
+            .init_resource::<PinIcons>()
+            .add_systems(OnEnter(AppState::Ready), collect_pin_icons)
+            .add_systems(
+                Update,
+                (
+                    create_pin,
+                    draw_pins.run_if(resource_changed::<Pins>()),
+                    change_pins_visibility,
+                    storage::save_resource::<Pins>(PINS_STORAGE_KEY)
+                        .run_if(resource_changed::<Pins>()),
+                ),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct PinSprite {
+    pub id: u64,
+    pub map_type: MapType,
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn collect_pin_icons(markers: Res<Markers>, mut pin_icons: ResMut<PinIcons>) {
+    let icons = pin_icons.icons_mut();
+    icons.extend(BASE_PIN_ICONS.iter().map(ToString::to_string));
+    for map_type in MapType::iter() {
+        for location in markers.locations(*map_type) {
+            for icon in location
+                .layers
+                .iter()
+                .filter_map(|layer| layer.icon.as_ref())
+            {
+                // Svg icons can't be used as sprites textures
+                if is_png(&icon.url) && !icons.contains(&icon.url) {
+                    icons.push(icon.url.clone());
+                }
+            }
+        }
+    }
+    icons.sort();
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn create_pin(
+    mut map_clicked: EventReader<MapClicked>,
+    map_type: Res<MapType>,
+    pins: Res<Pins>,
+    mut pin_editor: ResMut<PinEditor>,
+) {
+    for map_clicked in &mut map_clicked {
+        if map_clicked.button == MouseButton::Right {
+            let pin = pins.new_pin(
+                *map_type,
+                map_clicked.world_position,
+                DEFAULT_PIN_ICON.to_string(),
+            );
+            pin_editor.edit(pin);
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_pins(
+    mut commands: Commands,
+    assets_server: Res<AssetServer>,
+    pins: Res<Pins>,
+    pin_sprites: Query<Entity, With<PinSprite>>,
+) {
+    // There are few pins, it's simpler to respawn them all on change
+    for entity in &pin_sprites {
+        commands.entity(entity).despawn_recursive();
+    }
+    for map_type in MapType::iter() {
+        for pin in pins.pins(*map_type) {
+            commands
+                .spawn(SpatialBundle::from_transform(Transform::from_xyz(
+                    pin.pos.x, pin.pos.y, 150.0,
+                )))
+                .with_children(|commands| {
+                    commands.spawn(SpriteBundle {
+                        texture: assets_server.load(format!("icons/{}", pin.icon)),
+                        transform: Transform::from_scale(Vec3::splat(ICON_SCALE)),
+                        ..default()
+                    });
+                })
+                .insert(PinSprite {
+                    id: pin.id,
+                    map_type: pin.map_type,
+                });
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn change_pins_visibility(
+    displayed_markers: Res<DisplayedMarkers>,
+    map_type: Res<MapType>,
+    mut pin_sprites: Query<(&mut Visibility, &PinSprite)>,
+) {
+    for (mut pin_sprite_visibility, pin_sprite) in &mut pin_sprites {
+        *pin_sprite_visibility = if pin_sprite.map_type == *map_type
+            && displayed_markers.is_displayed(pin_sprite.map_type, PINS_CATEGORY)
+        {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
@@ -0,0 +1,186 @@
+//! The maps, described by the `assets/maps.json` manifest. A map is added by adding it to the
+//! manifest, along with its tiles and markers.
+
+use std::{ops::RangeInclusive, path::Path, sync::OnceLock};
+
+use anyhow::{bail, Context};
+use bevy::{math::Rect, prelude::Vec2, utils::HashSet};
+use serde::Deserialize;
+
+use crate::{marker_source::MarkersFileKind, resources::MapType};
+
+static REGISTRY: OnceLock<MapRegistry> = OnceLock::new();
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarkerFiles {
+    /// Relative to the markers directory
+    pub locations: String,
+    pub materials: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MapManifestEntry {
+    id: String,
+    name: String,
+    tile_root: String,
+    size_px: f32,
+    min_lod: u32,
+    max_lod: u32,
+    markers: MarkerFiles,
+    /// `[min_x, min_y, max_x, max_y]`, in world coordinates
+    camera_bounds: Option<[f32; 4]>,
+    #[serde(default = "meters_per_unit")]
+    meters_per_unit: f32,
+}
+
+fn meters_per_unit() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MapManifest {
+    default: String,
+    maps: Vec<MapManifestEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapInfo {
+    pub map_type: MapType,
+    /// Displayed in the UI
+    pub name: String,
+    /// The `{root}` of the tiles templates, relative to the assets directory
+    pub tile_root: String,
+    /// Side of the map, in world units, centered on the origin
+    pub size_px: f32,
+    pub lods: RangeInclusive<u32>,
+    pub markers: MarkerFiles,
+    /// In world coordinates, the camera can't be moved past them
+    pub camera_bounds: Rect,
+    /// In-game meters per world unit, 1 when the markers' coordinates are in world units
+    pub meters_per_unit: f32,
+}
+
+impl MapInfo {
+    #[must_use]
+    pub fn markers_file(&self, kind: MarkersFileKind) -> &str {
+        match kind {
+            MarkersFileKind::Locations => &self.markers.locations,
+            MarkersFileKind::Materials => &self.markers.materials,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapRegistry {
+    maps: Vec<MapInfo>,
+    map_types: Vec<MapType>,
+    default: MapType,
+}
+
+impl MapRegistry {
+    /// The registry used by [`MapType`], the bundled manifest's unless another one was installed
+    /// before
+    pub fn global() -> &'static Self {
+        REGISTRY.get_or_init(Self::bundled)
+    }
+
+    /// Makes this registry the global one, it fails once the global registry is used
+    #[allow(clippy::missing_errors_doc)]
+    pub fn install(self) -> anyhow::Result<()> {
+        REGISTRY
+            .set(self)
+            .map_err(|_| anyhow::anyhow!("the maps registry is already in use"))
+    }
+
+    /// The manifest of the assets, included in the binary
+    ///
+    /// # Panics
+    ///
+    /// If the manifest is invalid, which the tests rule out
+    #[must_use]
+    pub fn bundled() -> Self {
+        Self::from_json(include_str!("../assets/maps.json"))
+            .expect("the bundled maps manifest to be valid")
+    }
+
+    #[allow(clippy::missing_errors_doc)]
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("couldn't read {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("invalid maps manifest {}", path.display()))
+    }
+
+    #[allow(clippy::missing_errors_doc)]
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let manifest: MapManifest = serde_json::from_str(json)?;
+        if manifest.maps.is_empty() {
+            bail!("no maps");
+        }
+        let mut ids = HashSet::new();
+        let mut maps = Vec::new();
+        for map in manifest.maps {
+            if !ids.insert(map.id.clone()) {
+                bail!("duplicate map {}", map.id);
+            }
+            if map.min_lod > map.max_lod {
+                bail!("map {}: min_lod is greater than max_lod", map.id);
+            }
+            if map.size_px <= 0.0 {
+                bail!("map {}: size_px must be positive", map.id);
+            }
+            if map.meters_per_unit <= 0.0 {
+                bail!("map {}: meters_per_unit must be positive", map.id);
+            }
+            let camera_bounds = map.camera_bounds.map_or_else(
+                // Four times the map's size by default
+                || Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(map.size_px * 2.0)),
+                |[min_x, min_y, max_x, max_y]| Rect::new(min_x, min_y, max_x, max_y),
+            );
+            maps.push(MapInfo {
+                // The ids live as long as the app, the registry is loaded once
+                map_type: MapType::new(Box::leak(map.id.into_boxed_str())),
+                name: map.name,
+                tile_root: map.tile_root,
+                size_px: map.size_px,
+                lods: map.min_lod..=map.max_lod,
+                markers: map.markers,
+                camera_bounds,
+                meters_per_unit: map.meters_per_unit,
+            });
+        }
+        let map_types = maps.iter().map(|map| map.map_type).collect::<Vec<_>>();
+        let Some(default) = map_types
+            .iter()
+            .copied()
+            .find(|map_type| map_type.as_str() == manifest.default)
+        else {
+            bail!("unknown default map {}", manifest.default);
+        };
+        Ok(Self {
+            maps,
+            map_types,
+            default,
+        })
+    }
+
+    #[must_use]
+    pub fn maps(&self) -> &[MapInfo] {
+        &self.maps
+    }
+
+    /// In the manifest's order
+    #[must_use]
+    pub fn map_types(&self) -> &[MapType] {
+        &self.map_types
+    }
+
+    #[must_use]
+    pub fn default_map(&self) -> MapType {
+        self.default
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&MapInfo> {
+        self.maps.iter().find(|map| map.map_type.as_str() == id)
+    }
+}
@@ -1,19 +1,40 @@
-use std::{cmp::Ordering, fmt::Display, ops::Mul, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::Mul,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use bevy::{prelude::Resource, utils::HashSet};
+use bevy::{
+    prelude::{Entity, Resource, States, Vec2},
+    utils::{HashMap, HashSet},
+};
 
-use crate::types::{Location, Material};
+use lru::LruCache;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-pub const MAP_SIZE_PX: f32 = 12_000.0;
+use crate::{
+    coords::{tile_bounds, GameCoord},
+    marker_source::{read_markers, DirMarkerSource, MarkerSource, MarkersFileKind},
+    registry::{MapInfo, MapRegistry},
+    types::{Annotation, Location, LocationLayerMarker, Material, Pin, RulerPoint},
+};
 
 #[derive(Debug, Resource)]
 pub struct Options {
     pub debug_display: bool,
     /// Forwarded to Bevy's window plugin, change canvas selector in web/wasm mode
     pub canvas: Option<String>,
+    pub tile_budget: TileBudget,
+    pub tile_prefetch: TilePrefetch,
+    pub tile_layouts: TileLayouts,
+    /// The markers' files, the assets' markers directory on desktop and the markers included in
+    /// the binary on the web when `None`
+    pub markers_source: Option<DirMarkerSource>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Resource)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
 pub struct Lod(u32);
 
 impl PartialEq<u32> for Lod {
@@ -39,23 +60,40 @@ impl Mul<f32> for Lod {
 
 impl Lod {
     pub const MIN_VALUE: u32 = 0;
-    pub const MAX_VALUE: u32 = 6;
     pub const MIN: Lod = Lod(Self::MIN_VALUE);
 
     const SCALING_MAGIC_NUMBER: f32 = 2.0;
 
+    /// The lods available on a map are [`MapType::lods`]
+    #[must_use]
+    pub fn new(value: u32) -> Self {
+        Self(value)
+    }
+
+    #[must_use]
+    pub fn value(self) -> u32 {
+        self.0
+    }
+
+    /// The inverse of [`Lod::from_scale`], the camera scale at which this lod is used on the map,
+    /// 1 for its most detailed lod
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn scale(self, map_type: MapType) -> f32 {
+        ((map_type.max_lod().0 as f32 - self.0 as f32) / Self::SCALING_MAGIC_NUMBER).exp()
+    }
+
     #[allow(
         clippy::cast_sign_loss,
         clippy::cast_possible_truncation,
         clippy::cast_possible_wrap
     )]
     #[must_use]
-    pub fn from_scale(scale: f32) -> Self {
+    pub fn from_scale(scale: f32, map_type: MapType) -> Self {
+        let max_lod = map_type.max_lod().0 as i32;
         Self(
-            Self::MAX_VALUE
-                - ((scale.ln() * Self::SCALING_MAGIC_NUMBER).round() as i32)
-                    .max(Self::MIN_VALUE as i32)
-                    .min(Self::MAX_VALUE as i32) as u32,
+            (max_lod - (scale.ln() * Self::SCALING_MAGIC_NUMBER).round() as i32)
+                .clamp(map_type.min_lod().0 as i32, max_lod) as u32,
         )
     }
 
@@ -67,10 +105,11 @@ impl Lod {
 
     #[allow(clippy::cast_precision_loss)]
     #[must_use]
-    pub fn tile_px_size(self) -> f32 {
-        MAP_SIZE_PX / self.tiles_nb() as f32
+    pub fn tile_px_size(self, map_type: MapType) -> f32 {
+        map_type.size_px() / self.tiles_nb() as f32
     }
 
+    /// The index of the tile's column containing `pos`, or its row for `-y`
     #[allow(
         clippy::cast_sign_loss,
         clippy::cast_possible_truncation,
@@ -78,10 +117,10 @@ impl Lod {
         clippy::cast_possible_wrap
     )]
     #[must_use]
-    pub fn index(self, pos: f32) -> u32 {
-        let index =
-            ((pos + MAP_SIZE_PX / 2.0) / MAP_SIZE_PX * self.tiles_nb() as f32).floor() as u32;
-        index.max(0).min(self.tiles_nb() - 1)
+    pub fn index(self, pos: f32, map_type: MapType) -> u32 {
+        let size_px = map_type.size_px();
+        let index = ((pos + size_px / 2.0) / size_px * self.tiles_nb() as f32).floor() as u32;
+        index.min(self.tiles_nb() - 1)
     }
 }
 
@@ -91,81 +130,101 @@ impl Display for Lod {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
-pub enum MapType {
-    Sky,
-    Surface,
-    Depths,
-}
+/// A map of the [`MapRegistry`], identified by its id in the manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Resource)]
+pub struct MapType(&'static str);
 
 impl MapType {
+    /// The maps of the bundled manifest
+    pub const SKY: Self = Self("sky");
+    pub const SURFACE: Self = Self("surface");
+    pub const DEPTHS: Self = Self("depths");
+
+    /// Doesn't check that the map is in the registry, see [`MapType::from_str`]
+    #[must_use]
+    pub const fn new(id: &'static str) -> Self {
+        Self(id)
+    }
+
     #[must_use]
     pub fn as_str(self) -> &'static str {
-        match self {
-            Self::Sky => "sky",
-            Self::Surface => "surface",
-            Self::Depths => "depths",
-        }
+        self.0
     }
 
+    /// The maps of the registry, in the manifest's order
     #[must_use]
     pub fn iter() -> &'static [Self] {
-        &[MapType::Sky, MapType::Surface, MapType::Depths]
-    }
-
-    fn load_locations(self) -> anyhow::Result<Vec<Location>> {
-        #[cfg(not(target_arch = "wasm32"))]
-        let locations = {
-            let file = std::fs::File::open(format!("./assets/markers/{self}/locations.json"))?;
-            serde_json::from_reader(file)?
-        };
-        #[cfg(target_arch = "wasm32")]
-        let locations = match self {
-            Self::Sky => {
-                serde_json::from_str(include_str!("../assets/markers/sky/locations.json"))?
-            }
-            Self::Surface => {
-                serde_json::from_str(include_str!("../assets/markers/surface/locations.json"))?
-            }
-            Self::Depths => {
-                serde_json::from_str(include_str!("../assets/markers/depths/locations.json"))?
-            }
-        };
+        MapRegistry::global().map_types()
+    }
 
-        Ok(locations)
+    /// # Panics
+    ///
+    /// If the map isn't in the registry
+    #[must_use]
+    pub fn info(self) -> &'static MapInfo {
+        MapRegistry::global()
+            .get(self.0)
+            .unwrap_or_else(|| panic!("map {self} isn't in the registry"))
     }
 
-    fn load_materials(self) -> anyhow::Result<Vec<Material>> {
-        #[cfg(not(target_arch = "wasm32"))]
-        let materials = {
-            let file = std::fs::File::open(format!("./assets/markers/{self}/materials.json"))?;
-            serde_json::from_reader(file)?
-        };
-        #[cfg(target_arch = "wasm32")]
-        let materials = match self {
-            Self::Sky => {
-                serde_json::from_str(include_str!("../assets/markers/sky/materials.json"))?
-            }
-            Self::Surface => {
-                serde_json::from_str(include_str!("../assets/markers/surface/materials.json"))?
-            }
-            Self::Depths => {
-                serde_json::from_str(include_str!("../assets/markers/depths/materials.json"))?
-            }
-        };
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        &self.info().name
+    }
+
+    #[must_use]
+    pub fn size_px(self) -> f32 {
+        self.info().size_px
+    }
+
+    #[must_use]
+    pub fn meters_per_unit(self) -> f32 {
+        self.info().meters_per_unit
+    }
+
+    #[must_use]
+    pub fn min_lod(self) -> Lod {
+        Lod::new(*self.info().lods.start())
+    }
+
+    #[must_use]
+    pub fn max_lod(self) -> Lod {
+        Lod::new(*self.info().lods.end())
+    }
 
-        Ok(materials)
+    /// From the least to the most detailed
+    pub fn lods(self) -> impl Iterator<Item = Lod> {
+        self.info().lods.clone().map(Lod::new)
     }
 
+    /// With the default [`TileLayout`]
     #[must_use]
     pub fn tile_path(self, lod: Lod, x_idx: u32, y_idx: u32) -> PathBuf {
-        format!("tiles/{self}/{lod}/{x_idx}_{y_idx}.jpg").into()
+        TileLayout::default().path(self, lod, x_idx, y_idx)
     }
 }
 
 impl Default for MapType {
     fn default() -> Self {
-        Self::Surface
+        MapRegistry::global().default_map()
+    }
+}
+
+impl FromStr for MapType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::iter()
+            .iter()
+            .find(|map_type| map_type.as_str() == s)
+            .copied()
+            .ok_or_else(|| {
+                let ids = Self::iter()
+                    .iter()
+                    .map(|map_type| map_type.as_str())
+                    .collect::<Vec<_>>();
+                format!("unknown map {s}, expected one of {}", ids.join(", "))
+            })
     }
 }
 
@@ -175,130 +234,799 @@ impl Display for MapType {
     }
 }
 
-#[derive(Debug, Default, Resource)]
-pub struct SpawnedMarkers {
-    pub sky: bool,
-    pub surface: bool,
-    pub depths: bool,
+impl Serialize for MapType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
 }
 
+impl<'de> Deserialize<'de> for MapType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The maps whose markers were spawned
+#[derive(Debug, Default, Resource)]
+pub struct SpawnedMarkers(HashSet<MapType>);
+
 impl SpawnedMarkers {
     #[must_use]
     pub fn is_spawned(&self, map_type: MapType) -> bool {
-        match map_type {
-            MapType::Sky => self.sky,
-            MapType::Surface => self.surface,
-            MapType::Depths => self.depths,
-        }
+        self.0.contains(&map_type)
     }
 
     pub fn mark_spawned(&mut self, map_type: MapType) {
-        match map_type {
-            MapType::Sky => self.sky = true,
-            MapType::Surface => self.surface = true,
-            MapType::Depths => self.depths = true,
-        }
+        self.0.insert(map_type);
     }
 }
 
-#[derive(Debug, Resource)]
-pub struct Markers {
-    pub sky_locations: Vec<Location>,
-    pub surface_locations: Vec<Location>,
-    pub depths_locations: Vec<Location>,
-    pub sky_materials: Vec<Material>,
-    pub surface_materials: Vec<Material>,
-    pub depths_materials: Vec<Material>,
+#[derive(Debug, Default, PartialEq)]
+pub struct MapMarkers {
+    pub locations: Vec<Location>,
+    pub materials: Vec<Material>,
 }
 
+/// The markers of every map
+#[derive(Debug, Default, PartialEq, Resource)]
+pub struct Markers(HashMap<MapType, MapMarkers>);
+
 impl Markers {
     #[must_use]
     pub fn locations(&self, map_type: MapType) -> &[Location] {
-        match map_type {
-            MapType::Sky => &self.sky_locations,
-            MapType::Surface => &self.surface_locations,
-            MapType::Depths => &self.depths_locations,
-        }
+        self.0
+            .get(&map_type)
+            .map_or(&[], |markers| &markers.locations)
     }
 
     #[must_use]
     pub fn materials(&self, map_type: MapType) -> &[Material] {
-        match map_type {
-            MapType::Sky => &self.sky_materials,
-            MapType::Surface => &self.surface_materials,
-            MapType::Depths => &self.depths_materials,
-        }
+        self.0
+            .get(&map_type)
+            .map_or(&[], |markers| &markers.materials)
     }
 
-    #[allow(clippy::missing_errors_doc)]
-    pub fn load() -> anyhow::Result<Self> {
-        Ok(Self {
-            sky_locations: MapType::load_locations(MapType::Sky)?,
-            surface_locations: MapType::load_locations(MapType::Surface)?,
-            depths_locations: MapType::load_locations(MapType::Depths)?,
-            sky_materials: MapType::load_materials(MapType::Sky)?,
-            surface_materials: MapType::load_materials(MapType::Surface)?,
-            depths_materials: MapType::load_materials(MapType::Depths)?,
+    pub fn locations_mut(&mut self, map_type: MapType) -> &mut Vec<Location> {
+        &mut self.0.entry(map_type).or_default().locations
+    }
+
+    pub fn materials_mut(&mut self, map_type: MapType) -> &mut Vec<Material> {
+        &mut self.0.entry(map_type).or_default().materials
+    }
+
+    #[must_use]
+    pub fn location_marker(
+        &self,
+        map_type: MapType,
+        id: &str,
+    ) -> Option<(&Location, &LocationLayerMarker)> {
+        self.locations(map_type).iter().find_map(|location| {
+            location
+                .layers
+                .iter()
+                .flat_map(|layer| &layer.markers)
+                .find(|layer_marker| layer_marker.id == id)
+                .map(|layer_marker| (location, layer_marker))
         })
     }
+
+    /// Reads the locations and materials of every map of the registry from `source`
+    #[allow(clippy::missing_errors_doc)]
+    pub async fn load(source: &dyn MarkerSource) -> anyhow::Result<Self> {
+        let mut markers = Self::default();
+        for map_type in MapType::iter() {
+            *markers.locations_mut(*map_type) =
+                read_markers(source, *map_type, MarkersFileKind::Locations).await?;
+            *markers.materials_mut(*map_type) =
+                read_markers(source, *map_type, MarkersFileKind::Materials).await?;
+        }
+        Ok(markers)
+    }
 }
 
+/// The markers are loaded in the background, the map and markers systems run once they are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, States)]
+pub enum AppState {
+    #[default]
+    Loading,
+    Ready,
+    /// Some markers couldn't be loaded, see [`MarkersProgress::errors`]
+    Failed,
+}
+
+/// The markers' files loaded so far, by map
 #[derive(Debug, Default, Resource)]
-pub struct DisplayedMarkers(HashSet<String>);
+pub struct MarkersProgress {
+    loaded: HashMap<MapType, usize>,
+    pub errors: Vec<String>,
+}
+
+impl MarkersProgress {
+    pub fn file_loaded(&mut self, map_type: MapType) {
+        *self.loaded.entry(map_type).or_default() += 1;
+    }
+
+    /// From 0 to 1
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn progress(&self, map_type: MapType) -> f32 {
+        self.loaded.get(&map_type).copied().unwrap_or_default() as f32
+            / MarkersFileKind::iter().len() as f32
+    }
+}
+
+/// Seconds during which the notifications that aren't errors are displayed
+pub const NOTIFICATION_DURATION: f32 = 5.0;
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub message: String,
+    /// The errors are displayed until they are dismissed
+    pub is_error: bool,
+    /// Seconds since the app started
+    pub created_at: f32,
+}
+
+/// Messages displayed in a corner of the window, e.g. about the reloaded markers
+#[derive(Debug, Default, Resource)]
+pub struct Notifications(Vec<Notification>);
+
+impl Notifications {
+    pub fn info(&mut self, message: impl Into<String>, now: f32) {
+        self.push(message.into(), false, now);
+    }
+
+    pub fn error(&mut self, message: impl Into<String>, now: f32) {
+        self.push(message.into(), true, now);
+    }
+
+    fn push(&mut self, message: String, is_error: bool, now: f32) {
+        self.0.push(Notification {
+            message,
+            is_error,
+            created_at: now,
+        });
+    }
 
-impl DisplayedMarkers {
     #[must_use]
-    pub fn markers(&self) -> &HashSet<String> {
+    pub fn notifications(&self) -> &[Notification] {
         &self.0
     }
 
-    pub fn markers_mut(&mut self) -> &mut HashSet<String> {
-        &mut self.0
+    pub fn dismiss(&mut self, index: usize) {
+        self.0.remove(index);
+    }
+
+    pub fn remove_expired(&mut self, now: f32) {
+        self.0.retain(|notification| {
+            notification.is_error || now - notification.created_at < NOTIFICATION_DURATION
+        });
     }
+}
+
+/// The displayed markers categories, scoped by map so that categories sharing a name
+/// (e.g. "Cave") on different maps can be toggled independently
+#[derive(Debug, Default, Resource)]
+pub struct DisplayedMarkers {
+    markers: HashMap<MapType, HashSet<String>>,
+    pub hide_completed: bool,
+}
 
-    pub fn add_missing_from(&mut self, src: impl IntoIterator<Item = String>) {
-        self.0.extend(src);
+impl DisplayedMarkers {
+    #[must_use]
+    pub fn is_displayed(&self, map_type: MapType, marker: &str) -> bool {
+        self.markers
+            .get(&map_type)
+            .is_some_and(|markers| markers.contains(marker))
     }
 
-    pub fn remove_from<'a>(&mut self, src: impl IntoIterator<Item = &'a String>) {
+    pub fn markers_mut(&mut self, map_type: MapType) -> &mut HashSet<String> {
+        self.markers.entry(map_type).or_default()
+    }
+
+    pub fn add_missing_from(&mut self, map_type: MapType, src: impl IntoIterator<Item = String>) {
+        self.markers_mut(map_type).extend(src);
+    }
+
+    pub fn remove_from<'a>(
+        &mut self,
+        map_type: MapType,
+        src: impl IntoIterator<Item = &'a String>,
+    ) {
+        let markers = self.markers_mut(map_type);
         for marker in src {
-            self.0.remove(marker);
+            markers.remove(marker);
         }
     }
 
-    pub fn toggle(&mut self, marker: String) {
-        if self.0.contains(&marker) {
-            self.0.remove(&marker);
+    pub fn toggle(&mut self, map_type: MapType, marker: String) {
+        let markers = self.markers_mut(map_type);
+        if markers.contains(&marker) {
+            markers.remove(&marker);
         } else {
-            self.0.insert(marker);
+            markers.insert(marker);
+        }
+    }
+}
+
+/// Ids of the markers the user marked as done
+#[derive(Debug, Default, Resource, Serialize, Deserialize)]
+pub struct CompletedMarkers(HashSet<String>);
+
+impl CompletedMarkers {
+    #[must_use]
+    pub fn is_completed(&self, id: &str) -> bool {
+        self.0.contains(id)
+    }
+
+    pub fn toggle(&mut self, id: String) {
+        if self.0.contains(&id) {
+            self.0.remove(&id);
+        } else {
+            self.0.insert(id);
         }
     }
 }
 
 #[derive(Debug, Default, Resource)]
-pub struct FocusedMarkers(Vec<String>);
+pub struct FocusedMarkers(Vec<(Entity, String)>);
 
 impl FocusedMarkers {
     #[must_use]
-    pub fn markers(&self) -> &[String] {
+    pub fn markers(&self) -> &[(Entity, String)] {
         &self.0
     }
 
-    pub fn markers_mut(&mut self) -> &mut Vec<String> {
+    pub fn markers_mut(&mut self) -> &mut Vec<(Entity, String)> {
         &mut self.0
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct MarkerDetails {
+    pub map_type: MapType,
+    pub category: String,
+    pub name: Option<String>,
+    pub id: Option<String>,
+    pub coords: GameCoord,
+    pub source: Option<String>,
+    pub link: Option<String>,
+}
+
+/// The details are copied so that the selection doesn't depend on the marker entity
+#[derive(Debug, Default, Resource)]
+pub struct SelectedMarker(Option<MarkerDetails>);
+
+impl SelectedMarker {
+    #[must_use]
+    pub fn marker(&self) -> Option<&MarkerDetails> {
+        self.0.as_ref()
+    }
+
+    pub fn select(&mut self, marker: MarkerDetails) {
+        self.0 = Some(marker);
+    }
+
+    pub fn clear(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// Seconds during which the position reached with the "Go to" field is marked
+pub const CROSSHAIR_DURATION: f32 = 3.0;
+
+/// Marks the position reached with the "Go to" field, removed after [`CROSSHAIR_DURATION`]
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct Crosshair {
+    pub map_type: MapType,
+    /// In world coordinates
+    pub position: Vec2,
+    /// Seconds since the app started
+    pub created_at: f32,
+}
+
+#[derive(Debug, Default, Resource, Serialize, Deserialize)]
+pub struct Pins {
+    pins: Vec<Pin>,
+    next_id: u64,
+}
+
+impl Pins {
+    pub fn pins(&self, map_type: MapType) -> impl Iterator<Item = &Pin> {
+        self.pins.iter().filter(move |pin| pin.map_type == map_type)
+    }
+
+    /// The returned pin is not added, see [`Pins::upsert`]. Its id is the one it will be given, so
+    /// that cancelling its creation leaves the pins unchanged.
+    #[must_use]
+    pub fn new_pin(&self, map_type: MapType, pos: Vec2, icon: String) -> Pin {
+        Pin {
+            id: self.next_id,
+            map_type,
+            pos,
+            label: String::new(),
+            note: String::new(),
+            icon,
+        }
+    }
+
+    /// The pins that aren't added yet are given a new id
+    pub fn upsert(&mut self, mut pin: Pin) {
+        if let Some(existing_pin) = self.pins.iter_mut().find(|other| other.id == pin.id) {
+            *existing_pin = pin;
+        } else {
+            pin.id = self.next_id;
+            self.next_id += 1;
+            self.pins.push(pin);
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.pins.retain(|pin| pin.id != id);
+    }
+}
+
+/// The points clicked on the map while measuring, on a single map
 #[derive(Debug, Default, Resource)]
-pub struct LoadedTiles(Vec<PathBuf>);
+pub struct Ruler {
+    /// The clicks on the map add points rather than select markers
+    active: bool,
+    map_type: Option<MapType>,
+    points: Vec<RulerPoint>,
+}
+
+impl Ruler {
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
 
-impl LoadedTiles {
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// The map of the points, `None` when there are none
     #[must_use]
-    pub fn tiles(&self) -> &[PathBuf] {
+    pub fn map_type(&self) -> Option<MapType> {
+        self.map_type
+    }
+
+    #[must_use]
+    pub fn points(&self) -> &[RulerPoint] {
+        &self.points
+    }
+
+    /// A point on another map starts a new measurement
+    pub fn add_point(&mut self, map_type: MapType, point: RulerPoint) {
+        if self.map_type != Some(map_type) {
+            self.clear();
+            self.map_type = Some(map_type);
+        }
+        self.points.push(point);
+    }
+
+    pub fn remove_last_point(&mut self) {
+        self.points.pop();
+        if self.points.is_empty() {
+            self.map_type = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.map_type = None;
+    }
+}
+
+/// The measurements saved from the ruler
+#[derive(Debug, Default, Resource, Serialize, Deserialize)]
+pub struct Annotations {
+    annotations: Vec<Annotation>,
+    next_id: u64,
+}
+
+impl Annotations {
+    pub fn annotations(&self, map_type: MapType) -> impl Iterator<Item = &Annotation> {
+        self.annotations
+            .iter()
+            .filter(move |annotation| annotation.map_type == map_type)
+    }
+
+    pub fn add(&mut self, map_type: MapType, label: String, points: Vec<RulerPoint>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.annotations.push(Annotation {
+            id,
+            map_type,
+            label,
+            points,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.annotations.retain(|annotation| annotation.id != id);
+    }
+}
+
+/// The pin currently created or edited, if any
+#[derive(Debug, Default, Resource)]
+pub struct PinEditor(Option<Pin>);
+
+impl PinEditor {
+    pub fn pin_mut(&mut self) -> Option<&mut Pin> {
+        self.0.as_mut()
+    }
+
+    pub fn edit(&mut self, pin: Pin) {
+        self.0 = Some(pin);
+    }
+
+    pub fn close(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// File names, in `assets/icons`, of the icons available for the pins
+#[derive(Debug, Default, Resource)]
+pub struct PinIcons(Vec<String>);
+
+impl PinIcons {
+    #[must_use]
+    pub fn icons(&self) -> &[String] {
         &self.0
     }
 
-    pub fn tiles_mut(&mut self) -> &mut Vec<PathBuf> {
+    pub fn icons_mut(&mut self) -> &mut Vec<String> {
         &mut self.0
     }
 }
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TileFormat {
+    #[default]
+    Jpeg,
+    Png,
+    WebP,
+    Ktx2,
+}
+
+impl TileFormat {
+    /// From the most to the least preferred when several are present
+    #[must_use]
+    pub fn iter() -> &'static [Self] {
+        &[Self::Ktx2, Self::WebP, Self::Png, Self::Jpeg]
+    }
+
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::WebP => "webp",
+            Self::Ktx2 => "ktx2",
+        }
+    }
+}
+
+impl FromStr for TileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jpg" | "jpeg" => Ok(Self::Jpeg),
+            "png" => Ok(Self::Png),
+            "webp" => Ok(Self::WebP),
+            "ktx2" => Ok(Self::Ktx2),
+            _ => Err(format!(
+                "unknown tile format {s}, expected jpg, png, webp or ktx2"
+            )),
+        }
+    }
+}
+
+impl Display for TileFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Where a map's tiles are in the assets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileLayout {
+    /// Path with the `{root}` (the map's tile root), `{map}`, `{lod}` (or `{z}`), `{x}`, `{y}` and
+    /// `{ext}` placeholders, e.g. `{root}/{z}/{x}/{y}.{ext}` for the XYZ layout
+    pub template: String,
+    /// `None` until it's detected at startup, from the tiles present in the assets
+    pub format: Option<TileFormat>,
+}
+
+impl Default for TileLayout {
+    fn default() -> Self {
+        Self {
+            template: Self::DEFAULT_TEMPLATE.to_string(),
+            format: Some(TileFormat::Jpeg),
+        }
+    }
+}
+
+impl TileLayout {
+    pub const DEFAULT_TEMPLATE: &'static str = "{root}/{lod}/{x}_{y}.{ext}";
+    pub const XYZ_TEMPLATE: &'static str = "{root}/{z}/{x}/{y}.{ext}";
+
+    /// The tile's path, relative to the assets directory. An undetected format is a JPEG.
+    #[must_use]
+    pub fn path(&self, map_type: MapType, lod: Lod, x_idx: u32, y_idx: u32) -> PathBuf {
+        self.path_with_format(self.format.unwrap_or_default(), map_type, lod, x_idx, y_idx)
+    }
+
+    #[must_use]
+    pub fn path_with_format(
+        &self,
+        format: TileFormat,
+        map_type: MapType,
+        lod: Lod,
+        x_idx: u32,
+        y_idx: u32,
+    ) -> PathBuf {
+        self.template
+            .replace("{root}", &map_type.info().tile_root)
+            .replace("{map}", map_type.as_str())
+            .replace("{lod}", &lod.to_string())
+            .replace("{z}", &lod.to_string())
+            .replace("{x}", &x_idx.to_string())
+            .replace("{y}", &y_idx.to_string())
+            .replace("{ext}", format.extension())
+            .into()
+    }
+
+    /// The first format, in [`TileFormat::iter`] order, for which the map's lowest lod tile is in
+    /// `assets_dir`
+    #[must_use]
+    pub fn detect_format(&self, map_type: MapType, assets_dir: &Path) -> Option<TileFormat> {
+        TileFormat::iter().iter().copied().find(|format| {
+            assets_dir
+                .join(self.path_with_format(*format, map_type, map_type.min_lod(), 0, 0))
+                .is_file()
+        })
+    }
+}
+
+/// The tile layout of every map
+#[derive(Debug, Clone, Resource)]
+pub struct TileLayouts(HashMap<MapType, TileLayout>);
+
+impl Default for TileLayouts {
+    fn default() -> Self {
+        Self(
+            MapType::iter()
+                .iter()
+                .map(|map_type| (*map_type, TileLayout::default()))
+                .collect(),
+        )
+    }
+}
+
+impl TileLayouts {
+    #[must_use]
+    pub fn get(&self, map_type: MapType) -> &TileLayout {
+        &self.0[&map_type]
+    }
+
+    pub fn get_mut(&mut self, map_type: MapType) -> &mut TileLayout {
+        self.0.entry(map_type).or_default()
+    }
+
+    pub fn insert(&mut self, map_type: MapType, layout: TileLayout) {
+        self.0.insert(map_type, layout);
+    }
+}
+
+/// Identifies a tile, see [`TileLayout::path`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub map_type: MapType,
+    pub lod: Lod,
+    pub x_idx: u32,
+    pub y_idx: u32,
+}
+
+impl TileKey {
+    /// The tile covering this one at the previous lod, `None` at the map's lowest lod
+    #[must_use]
+    pub fn parent(self) -> Option<Self> {
+        (self.lod > self.map_type.min_lod()).then(|| Self {
+            map_type: self.map_type,
+            lod: Lod::new(self.lod.value() - 1),
+            x_idx: self.x_idx / 2,
+            y_idx: self.y_idx / 2,
+        })
+    }
+
+    /// In world coordinates
+    #[must_use]
+    pub fn center(self) -> Vec2 {
+        tile_bounds(self).center()
+    }
+}
+
+/// Side of the tiles images, at every lod
+pub const TILE_SIZE_PX: u32 = 564;
+/// Memory used by a decoded tile image, as RGBA
+pub const TILE_MEMORY_BYTES: usize = (TILE_SIZE_PX * TILE_SIZE_PX * 4) as usize;
+
+/// How many tiles can stay loaded
+#[derive(Debug, Clone, Copy)]
+pub enum TileBudget {
+    Tiles(usize),
+    Megabytes(usize),
+}
+
+impl Default for TileBudget {
+    fn default() -> Self {
+        Self::Megabytes(256)
+    }
+}
+
+impl TileBudget {
+    #[must_use]
+    pub fn max_tiles(self) -> usize {
+        match self {
+            Self::Tiles(tiles) => tiles,
+            Self::Megabytes(megabytes) => megabytes * 1024 * 1024 / TILE_MEMORY_BYTES,
+        }
+    }
+}
+
+/// Which hidden tiles are loaded ahead of being displayed
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct TilePrefetch {
+    /// Width, in tiles, of the ring around the visible tiles at the current lod
+    pub ring: u32,
+    /// How far ahead, in seconds, the camera's movement is anticipated
+    pub look_ahead: f32,
+    /// Prefetched tiles requested per frame, after the visible ones
+    pub max_per_frame: usize,
+}
+
+impl Default for TilePrefetch {
+    fn default() -> Self {
+        Self {
+            ring: 1,
+            look_ahead: 0.5,
+            max_per_frame: 4,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TileCacheStats {
+    /// Tiles shown again from the cache
+    pub hits: u64,
+    /// Tiles that had to be loaded
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// The loaded tiles' entities, from the most to the least recently visible
+#[derive(Debug, Resource)]
+pub struct TileCache {
+    /// With the frame at which each tile was last visible
+    tiles: LruCache<TileKey, (Entity, u64)>,
+    /// The maps' least detailed tiles, the last resort fallback, kept out of the budget
+    pinned: HashMap<TileKey, Entity>,
+    max_tiles: usize,
+    frame: u64,
+    stats: TileCacheStats,
+}
+
+impl TileCache {
+    #[must_use]
+    pub fn new(budget: TileBudget) -> Self {
+        Self {
+            // The cache is bounded by the eviction, which keeps the visible tiles
+            tiles: LruCache::unbounded(),
+            pinned: HashMap::default(),
+            max_tiles: budget.max_tiles(),
+            frame: 0,
+            stats: TileCacheStats::default(),
+        }
+    }
+
+    /// Records a tile loaded to be displayed, counted as a miss
+    pub fn insert(&mut self, key: TileKey, entity: Entity) {
+        self.tiles.put(key, (entity, self.frame));
+        self.stats.misses += 1;
+    }
+
+    /// Records a tile loaded ahead of being displayed, counted as a miss. It was never visible, so
+    /// it's the least recently visible tile until it's touched.
+    pub fn insert_prefetched(&mut self, key: TileKey, entity: Entity) {
+        self.insert(key, entity);
+        self.tiles.demote(&key);
+    }
+
+    /// Records a tile loaded to stay, counted as a miss. It's never evicted.
+    pub fn insert_pinned(&mut self, key: TileKey, entity: Entity) {
+        self.pinned.insert(key, entity);
+        self.stats.misses += 1;
+    }
+
+    /// Marks a loaded tile as visible in the current frame. It's a hit if the tile is shown again,
+    /// after having been hidden since the previous frame at least.
+    pub fn touch(&mut self, key: TileKey) {
+        let frame = self.frame;
+        if let Some((_, last_visible)) = self.tiles.get_mut(&key) {
+            if *last_visible + 1 < frame {
+                self.stats.hits += 1;
+            }
+            *last_visible = frame;
+        }
+    }
+
+    /// Starts a new frame, the tiles that are still visible must be touched again
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Removes the least recently visible tiles until the budget is met, the tiles visible or
+    /// loaded in the current frame are never evicted even if they exceed the budget
+    pub fn evict(&mut self) -> Vec<(TileKey, Entity)> {
+        let excess = self.tiles.len().saturating_sub(self.max_tiles);
+        let evicted_keys = self
+            .tiles
+            .iter()
+            .rev()
+            .filter(|(_, (_, last_visible))| *last_visible < self.frame)
+            .take(excess)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        let evicted = evicted_keys
+            .into_iter()
+            .filter_map(|key| self.tiles.pop(&key).map(|(entity, _)| (key, entity)))
+            .collect::<Vec<_>>();
+        self.stats.evictions += evicted.len() as u64;
+        evicted
+    }
+
+    /// The tile's entity if it's loaded, without making it more recently visible
+    #[must_use]
+    pub fn peek(&self, key: TileKey) -> Option<Entity> {
+        self.tiles
+            .peek(&key)
+            .map(|(entity, _)| *entity)
+            .or_else(|| self.pinned.get(&key).copied())
+    }
+
+    #[must_use]
+    pub fn contains(&self, key: TileKey) -> bool {
+        self.tiles.contains(&key) || self.pinned.contains_key(&key)
+    }
+
+    /// The tiles that can be evicted, the pinned ones aren't counted
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    #[must_use]
+    pub fn pinned_len(&self) -> usize {
+        self.pinned.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    #[must_use]
+    pub fn max_tiles(&self) -> usize {
+        self.max_tiles
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> TileCacheStats {
+        self.stats
+    }
+}
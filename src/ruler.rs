@@ -0,0 +1,172 @@
+//! Measuring distances by clicking points on the map. The points snap to the hovered marker, whose
+//! elevation gives the elevation difference, and the measurements can be saved as annotations.
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::{MainCamera, MapClicked},
+    markers::{marker_at, MarkerSprite},
+    resources::{Annotations, AppState, MapType, Ruler},
+    spatial::MarkersIndex,
+    storage,
+    types::RulerPoint,
+};
+
+const ANNOTATIONS_STORAGE_KEY: &str = "annotations";
+const RULER_COLOR: Color = Color::ORANGE;
+const ANNOTATION_COLOR: Color = Color::CYAN;
+/// Radius of the points' circles, in pixels
+const POINT_RADIUS: f32 = 4.0;
+
+pub struct RulerPlugin;
+
+impl Plugin for RulerPlugin {
+    fn build(&self, app: &mut App) {
+        let annotations = storage::load(ANNOTATIONS_STORAGE_KEY)
+            .unwrap_or_else(|err| {
+                error!("couldn't load the annotations: {err:#}");
+                None
+            })
+            .unwrap_or_default();
+        app.insert_resource::<Annotations>(annotations)
+            .init_resource::<Ruler>()
+            .add_systems(
+                Update,
+                (
+                    add_ruler_point.run_if(ruler_is_active),
+                    draw_ruler,
+                    draw_annotations,
+                    storage::save_resource::<Annotations>(ANNOTATIONS_STORAGE_KEY)
+                        .run_if(resource_changed::<Annotations>()),
+                )
+                    .run_if(in_state(AppState::Ready)),
+            );
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[must_use]
+pub fn ruler_is_active(ruler: Res<Ruler>) -> bool {
+    ruler.is_active()
+}
+
+/// The length of each segment, in in-game meters
+#[must_use]
+pub fn segment_lengths(points: &[RulerPoint], map_type: MapType) -> Vec<f32> {
+    points
+        .windows(2)
+        .map(|segment| segment[0].pos.distance(segment[1].pos) * map_type.meters_per_unit())
+        .collect()
+}
+
+/// In in-game meters
+#[must_use]
+pub fn total_length(points: &[RulerPoint], map_type: MapType) -> f32 {
+    segment_lengths(points, map_type).iter().sum()
+}
+
+/// From the first point to the last, when both snapped to a marker
+#[must_use]
+pub fn elevation_difference(points: &[RulerPoint]) -> Option<f32> {
+    match points {
+        [first, .., last] => Some(last.elevation? - first.elevation?),
+        _ => None,
+    }
+}
+
+/// In meters, or kilometers past 1000 meters
+#[must_use]
+pub fn format_distance(meters: f32) -> String {
+    if meters < 1_000.0 {
+        format!("{meters:.0} m")
+    } else {
+        format!("{:.2} km", meters / 1_000.0)
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn add_ruler_point(
+    mut map_clicked: EventReader<MapClicked>,
+    map_type: Res<MapType>,
+    markers_index: Res<MarkersIndex>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+    // The clusters have no elevation, only the markers are snapped to
+    clickable_markers: Query<(&Visibility, &Transform), With<MarkerSprite>>,
+    marker_sprites: Query<(&MarkerSprite, &Transform)>,
+    mut ruler: ResMut<Ruler>,
+) {
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+    for map_clicked in &mut map_clicked {
+        if map_clicked.button != MouseButton::Left {
+            continue;
+        }
+        let snapped_marker = marker_at(
+            &markers_index,
+            *map_type,
+            map_clicked.world_position,
+            projection.scale,
+            &clickable_markers,
+        )
+        .and_then(|entity| marker_sprites.get(entity).ok());
+        let point = match snapped_marker {
+            Some((marker_sprite, transform)) => RulerPoint {
+                pos: transform.translation.truncate(),
+                elevation: Some(marker_sprite.coords.elevation),
+            },
+            None => RulerPoint {
+                pos: map_clicked.world_position,
+                elevation: None,
+            },
+        };
+        ruler.add_point(*map_type, point);
+    }
+}
+
+fn draw_points(
+    gizmos: &mut Gizmos,
+    points: &[RulerPoint],
+    projection: &OrthographicProjection,
+    color: Color,
+) {
+    gizmos.linestrip_2d(points.iter().map(|point| point.pos), color);
+    for point in points {
+        gizmos.circle_2d(point.pos, POINT_RADIUS * projection.scale, color);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_ruler(
+    mut gizmos: Gizmos,
+    ruler: Res<Ruler>,
+    map_type: Res<MapType>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+) {
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+    if ruler.map_type() == Some(*map_type) {
+        draw_points(&mut gizmos, ruler.points(), projection, RULER_COLOR);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn draw_annotations(
+    mut gizmos: Gizmos,
+    annotations: Res<Annotations>,
+    map_type: Res<MapType>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+) {
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+    for annotation in annotations.annotations(*map_type) {
+        draw_points(
+            &mut gizmos,
+            &annotation.points,
+            projection,
+            ANNOTATION_COLOR,
+        );
+    }
+}
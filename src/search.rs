@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+
+use crate::{
+    coords::GameCoord,
+    resources::{AppState, Lod, MapType, Markers},
+    types::LocationLayer,
+};
+
+/// Lod at which the camera is centered on a single marker, if its layer is displayed at it
+const MARKER_LOD: u32 = 5;
+const MAX_RESULTS: usize = 50;
+
+pub struct SearchPlugin;
+
+impl Plugin for SearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SearchIndex>().add_systems(
+            Update,
+            build_search_index
+                .run_if(in_state(AppState::Ready).and_then(resource_changed::<Markers>())),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct SearchEntry {
+    pub map_type: MapType,
+    pub label: String,
+    /// The markers category to display when the entry is chosen
+    pub category: String,
+    /// In world coordinates
+    pub position: Vec2,
+    pub lod: Lod,
+    /// Lowercased texts matched against the query, from the most to the least relevant
+    keys: Vec<String>,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct SearchIndex(Vec<SearchEntry>);
+
+impl SearchIndex {
+    #[must_use]
+    pub fn from_markers(markers: &Markers) -> Self {
+        let mut entries = Vec::new();
+        for map_type in MapType::iter() {
+            for location in markers.locations(*map_type) {
+                let layer_markers = location.layers.iter().flat_map(|layer| {
+                    layer
+                        .markers
+                        .iter()
+                        .map(move |layer_marker| (layer, layer_marker))
+                });
+                let mut positions = Vec::new();
+                for (layer, layer_marker) in layer_markers {
+                    let position = GameCoord::from_json(layer_marker.pos, layer_marker.elv)
+                        .to_world()
+                        .0;
+                    positions.push(position);
+                    let name = layer_marker.name.as_deref().unwrap_or(&location.name);
+                    entries.push(SearchEntry {
+                        map_type: *map_type,
+                        label: format!("{name} ({})", layer_marker.id),
+                        category: location.name.clone(),
+                        position,
+                        lod: Self::marker_lod(*map_type, layer),
+                        keys: vec![
+                            name.to_lowercase(),
+                            layer_marker.id.to_lowercase(),
+                            location.name.to_lowercase(),
+                        ],
+                    });
+                }
+                entries.extend(Self::category_entry(*map_type, &location.name, &positions));
+            }
+
+            for material in markers.materials(*map_type) {
+                let positions = material
+                    .pos
+                    .iter()
+                    .map(|pos| GameCoord::from_material_pos(*pos).to_world().0)
+                    .collect::<Vec<_>>();
+                entries.extend(Self::category_entry(*map_type, &material.name, &positions));
+            }
+        }
+
+        Self(entries)
+    }
+
+    /// The lod closest to [`MARKER_LOD`] at which the layer's markers are displayed on the map
+    fn marker_lod(map_type: MapType, layer: &LocationLayer) -> Lod {
+        Lod::new(MARKER_LOD.max(layer.min_lod).min(layer.max_lod))
+            .max(map_type.min_lod())
+            .min(map_type.max_lod())
+    }
+
+    /// An entry covering a whole category, centered on its markers
+    #[allow(clippy::cast_precision_loss)]
+    fn category_entry(map_type: MapType, name: &str, positions: &[Vec2]) -> Option<SearchEntry> {
+        if positions.is_empty() {
+            return None;
+        }
+        Some(SearchEntry {
+            map_type,
+            label: format!("{name} (all)"),
+            category: name.to_string(),
+            position: positions.iter().sum::<Vec2>() / positions.len() as f32,
+            lod: map_type.min_lod(),
+            keys: vec![name.to_lowercase()],
+        })
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[SearchEntry] {
+        &self.0
+    }
+
+    /// Indexes of the best matching entries, best first
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    #[must_use]
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut results = self
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                entry
+                    .keys
+                    .iter()
+                    .enumerate()
+                    // Matches on the less relevant keys are slightly penalized
+                    .filter_map(|(rank, key)| {
+                        fuzzy_score(&query, key).map(|score| score - 2 * rank as i32)
+                    })
+                    .max()
+                    .map(|score| (index, score))
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|(index, score), (other_index, other_score)| {
+            other_score
+                .cmp(score)
+                .then_with(|| self.0[*index].label.cmp(&self.0[*other_index].label))
+        });
+        results.truncate(MAX_RESULTS);
+
+        results.into_iter().map(|(index, _)| index).collect()
+    }
+}
+
+/// Scores how well `candidate` matches `query`, `None` if all the query's characters can't be
+/// found in order in `candidate`. Consecutive characters, words starts and prefixes score higher.
+/// Both are expected to be lowercased.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+#[must_use]
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    if candidate == query {
+        return Some(1_000);
+    }
+
+    let mut score = 0;
+    let mut candidate_chars = candidate.chars().enumerate();
+    let mut previous_match = None;
+    let mut previous_char = None;
+    for query_char in query.chars() {
+        loop {
+            let (index, candidate_char) = candidate_chars.next()?;
+            let is_word_start = !previous_char.is_some_and(char::is_alphanumeric);
+            previous_char = Some(candidate_char);
+            if candidate_char != query_char {
+                continue;
+            }
+            score += 1;
+            if previous_match.is_some_and(|previous_index| previous_index + 1 == index) {
+                score += 5;
+            }
+            if is_word_start {
+                score += 10;
+            }
+            previous_match = Some(index);
+            break;
+        }
+    }
+
+    if candidate.starts_with(query) {
+        score += 20;
+    }
+    // Shorter candidates are closer to the query
+    score -= (candidate.chars().count() / 8) as i32;
+
+    Some(score)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn build_search_index(markers: Res<Markers>, mut search_index: ResMut<SearchIndex>) {
+    *search_index = SearchIndex::from_markers(&markers);
+}
@@ -0,0 +1,264 @@
+use bevy::{math::Rect, prelude::*, utils::HashMap};
+
+use crate::resources::MapType;
+
+const NODE_CAPACITY: usize = 32;
+const MAX_DEPTH: usize = 16;
+
+/// Point quadtree. The points are dispatched to the children by comparing them to the node's
+/// center, and every node keeps the bounding box of the points it contains, so any point can be
+/// inserted, even outside of the initial bounds.
+#[derive(Debug)]
+pub struct QuadTree<T> {
+    root: Node<T>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    center: Vec2,
+    half_size: Vec2,
+    /// Bounding box of the points in the node and its children, `None` when empty
+    extent: Option<Rect>,
+    items: Vec<(Vec2, T)>,
+    children: Option<Box<[Node<T>; 4]>>,
+}
+
+impl<T: Copy> QuadTree<T> {
+    #[must_use]
+    pub fn new(bounds: Rect) -> Self {
+        Self {
+            root: Node::new(bounds.center(), bounds.half_size()),
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, pos: Vec2, item: T) {
+        self.root.insert(pos, item, 0);
+        self.len += 1;
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Items whose position is in `rect`, bounds included
+    #[must_use]
+    pub fn query_rect(&self, rect: Rect) -> Vec<T> {
+        let mut results = Vec::new();
+        self.root.visit(rect, &mut |_, item| results.push(item));
+        results
+    }
+
+    /// The closest item at `max_distance` or less from `pos`
+    #[must_use]
+    pub fn nearest(&self, pos: Vec2, max_distance: f32) -> Option<T> {
+        self.nearest_by(pos, max_distance, 0.0, |item_pos, _| Some(item_pos))
+    }
+
+    /// The closest item at `max_distance` or less from `pos`, at the position returned by
+    /// `position`, `None` to skip the item. The returned positions must be at `margin` or less
+    /// from the indexed ones.
+    #[must_use]
+    pub fn nearest_by(
+        &self,
+        pos: Vec2,
+        max_distance: f32,
+        margin: f32,
+        mut position: impl FnMut(Vec2, T) -> Option<Vec2>,
+    ) -> Option<T> {
+        let mut best = None;
+        self.root.nearest(
+            pos,
+            &mut best,
+            max_distance * max_distance,
+            margin,
+            &mut position,
+        );
+        best.map(|(item, _)| item)
+    }
+}
+
+impl<T: Copy> FromIterator<(Vec2, T)> for QuadTree<T> {
+    fn from_iter<I: IntoIterator<Item = (Vec2, T)>>(iter: I) -> Self {
+        let items = iter.into_iter().collect::<Vec<_>>();
+        let bounds = items
+            .iter()
+            .fold(None, |bounds: Option<Rect>, (pos, _)| {
+                Some(bounds.map_or_else(|| Rect::from_corners(*pos, *pos), |b| b.union_point(*pos)))
+            })
+            .unwrap_or_default();
+        let mut quad_tree = Self::new(bounds);
+        for (pos, item) in items {
+            quad_tree.insert(pos, item);
+        }
+        quad_tree
+    }
+}
+
+impl<T: Copy> Node<T> {
+    fn new(center: Vec2, half_size: Vec2) -> Self {
+        Self {
+            center,
+            half_size,
+            extent: None,
+            items: Vec::new(),
+            children: None,
+        }
+    }
+
+    fn child_index(&self, pos: Vec2) -> usize {
+        usize::from(pos.x >= self.center.x) | (usize::from(pos.y >= self.center.y) << 1)
+    }
+
+    fn insert(&mut self, pos: Vec2, item: T, depth: usize) {
+        self.extent = Some(self.extent.map_or_else(
+            || Rect::from_corners(pos, pos),
+            |extent| extent.union_point(pos),
+        ));
+
+        let index = self.child_index(pos);
+        if let Some(children) = &mut self.children {
+            children[index].insert(pos, item, depth + 1);
+            return;
+        }
+
+        self.items.push((pos, item));
+        if self.items.len() > NODE_CAPACITY && depth < MAX_DEPTH {
+            self.split(depth);
+        }
+    }
+
+    fn split(&mut self, depth: usize) {
+        let half_size = self.half_size / 2.0;
+        // Same order as `child_index`
+        let mut children = Box::new(
+            [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+                .map(|(x, y)| Node::new(self.center + half_size * Vec2::new(x, y), half_size)),
+        );
+        for (pos, item) in std::mem::take(&mut self.items) {
+            children[self.child_index(pos)].insert(pos, item, depth + 1);
+        }
+        self.children = Some(children);
+    }
+
+    fn visit(&self, rect: Rect, f: &mut impl FnMut(Vec2, T)) {
+        let Some(extent) = self.extent else {
+            return;
+        };
+        if !intersects(extent, rect) {
+            return;
+        }
+        for (pos, item) in &self.items {
+            if contains(rect, *pos) {
+                f(*pos, *item);
+            }
+        }
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                child.visit(rect, f);
+            }
+        }
+    }
+
+    fn nearest(
+        &self,
+        pos: Vec2,
+        best: &mut Option<(T, f32)>,
+        max_distance_squared: f32,
+        margin: f32,
+        position: &mut impl FnMut(Vec2, T) -> Option<Vec2>,
+    ) {
+        let Some(extent) = self.extent else {
+            return;
+        };
+        let limit = best.map_or(max_distance_squared, |(_, distance)| distance);
+        if distance_squared_to_rect(extent.inset(margin), pos) > limit {
+            return;
+        }
+
+        for (item_pos, item) in &self.items {
+            let Some(item_pos) = position(*item_pos, *item) else {
+                continue;
+            };
+            let distance = item_pos.distance_squared(pos);
+            if distance <= best.map_or(max_distance_squared, |(_, distance)| distance) {
+                *best = Some((*item, distance));
+            }
+        }
+
+        if let Some(children) = &self.children {
+            // The child containing `pos` most likely has the closest item, which prunes the others
+            let first = self.child_index(pos);
+            children[first].nearest(pos, best, max_distance_squared, margin, position);
+            for (index, child) in children.iter().enumerate() {
+                if index != first {
+                    child.nearest(pos, best, max_distance_squared, margin, position);
+                }
+            }
+        }
+    }
+}
+
+/// Unlike `Rect::intersect`, degenerated rects (e.g. a single point) are supported
+fn intersects(rect: Rect, other: Rect) -> bool {
+    rect.min.x <= other.max.x
+        && other.min.x <= rect.max.x
+        && rect.min.y <= other.max.y
+        && other.min.y <= rect.max.y
+}
+
+fn contains(rect: Rect, pos: Vec2) -> bool {
+    pos.cmpge(rect.min).all() && pos.cmple(rect.max).all()
+}
+
+fn distance_squared_to_rect(rect: Rect, pos: Vec2) -> f32 {
+    pos.distance_squared(pos.clamp(rect.min, rect.max))
+}
+
+/// The spatial index of one map's markers entities
+#[derive(Debug)]
+pub struct MapMarkersIndex {
+    pub quad_tree: QuadTree<Entity>,
+    /// How far the markers can be moved from their indexed position (see `zoomAdjustedCoords`),
+    /// the queries must be extended by this distance
+    pub margin: f32,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct MarkersIndex(HashMap<MapType, MapMarkersIndex>);
+
+impl MarkersIndex {
+    #[must_use]
+    pub fn get(&self, map_type: MapType) -> Option<&MapMarkersIndex> {
+        self.0.get(&map_type)
+    }
+
+    pub fn insert(&mut self, map_type: MapType, index: MapMarkersIndex) {
+        self.0.insert(map_type, index);
+    }
+
+    /// The map's marker closest to `pos`, at the position returned by `position`, `None` to skip
+    /// the marker (e.g. when it's hidden)
+    #[must_use]
+    pub fn nearest(
+        &self,
+        map_type: MapType,
+        pos: Vec2,
+        max_distance: f32,
+        mut position: impl FnMut(Entity) -> Option<Vec2>,
+    ) -> Option<Entity> {
+        let index = self.get(map_type)?;
+        index
+            .quad_tree
+            .nearest_by(pos, max_distance, index.margin, |_, entity| {
+                position(entity)
+            })
+    }
+}
@@ -0,0 +1,91 @@
+//! Persistence of the user data (progress, pins...), as json files in the user's data directory
+//! on desktop, and in the local storage on the web.
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Content that can't be deserialized (e.g. pins on a map removed from the manifest) is copied
+/// under `{key}.invalid` first, since the resource falling back to its default overwrites it
+#[allow(clippy::missing_errors_doc)]
+pub fn load<T: DeserializeOwned>(key: &str) -> anyhow::Result<Option<T>> {
+    let Some(content) = read(key)? else {
+        return Ok(None);
+    };
+    match serde_json::from_str(&content) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => {
+            let backup_key = format!("{key}.invalid");
+            write(&backup_key, &content)?;
+            Err(anyhow::Error::new(err).context(format!("{key} was backed up as {backup_key}")))
+        }
+    }
+}
+
+#[allow(clippy::missing_errors_doc)]
+pub fn save<T: Serialize>(key: &str, value: &T) -> anyhow::Result<()> {
+    write(key, &serde_json::to_string(value)?)
+}
+
+/// A system saving the resource under `key`, to run when the resource changed
+pub fn save_resource<R: Resource + Serialize>(key: &'static str) -> impl FnMut(Res<R>) {
+    move |resource: Res<R>| {
+        // Nothing to save when the resource has just been loaded
+        if resource.is_added() {
+            return;
+        }
+        if let Err(err) = save(key, &*resource) {
+            error!("couldn't save {key}: {err}");
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(key: &str) -> anyhow::Result<std::path::PathBuf> {
+    use anyhow::Context;
+
+    let dirs = directories::ProjectDirs::from("", "", "totk-map")
+        .context("user's data directory to be available")?;
+    Ok(dirs.data_dir().join(format!("{key}.json")))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> anyhow::Result<Option<String>> {
+    let path = path(key)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(std::fs::read_to_string(path)?))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: &str, content: &str) -> anyhow::Result<()> {
+    let path = path(key)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> anyhow::Result<web_sys::Storage> {
+    use anyhow::Context;
+
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .context("local storage to be available")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> anyhow::Result<Option<String>> {
+    local_storage()?
+        .get_item(&format!("totk-map.{key}"))
+        .map_err(|err| anyhow::anyhow!("couldn't read {key} from the local storage: {err:?}"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(key: &str, content: &str) -> anyhow::Result<()> {
+    local_storage()?
+        .set_item(&format!("totk-map.{key}"), content)
+        .map_err(|err| anyhow::anyhow!("couldn't write {key} to the local storage: {err:?}"))
+}
@@ -0,0 +1,216 @@
+//! Generation of the map tiles, used by the `totk tiles build` subcommand.
+
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    ops::RangeInclusive,
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context};
+use image::{
+    codecs::jpeg::JpegEncoder,
+    imageops::{self, FilterType},
+    io::Reader,
+    RgbImage,
+};
+
+use crate::resources::{Lod, MapType, TILE_SIZE_PX};
+
+#[derive(Debug, Clone)]
+pub struct PyramidOptions {
+    /// JPEG quality, from 1 to 100
+    pub quality: u8,
+    pub filter: FilterType,
+    /// Keeps the tiles more recent than the source image
+    pub skip_up_to_date: bool,
+    /// The lods to build, all of the map's by default
+    pub lods: Option<RangeInclusive<u32>>,
+}
+
+impl Default for PyramidOptions {
+    fn default() -> Self {
+        Self {
+            quality: 85,
+            filter: FilterType::Lanczos3,
+            skip_up_to_date: false,
+            lods: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PyramidReport {
+    pub written: usize,
+    pub skipped: usize,
+}
+
+/// Writes the tiles of the lods in `assets_dir`, named like [`MapType::tile_path`], from a
+/// `source` image covering the whole map. The most detailed lod's tiles are resized from their area
+/// of the source, and each of the other lods' from the 4 tiles written at the next lod, so that only
+/// the source and a few tiles are in memory. `on_lod_built` is called after each lod, from the most
+/// detailed, to report the progress.
+#[allow(clippy::missing_errors_doc)]
+pub fn build_pyramid(
+    source: &Path,
+    map_type: MapType,
+    assets_dir: &Path,
+    options: &PyramidOptions,
+    mut on_lod_built: impl FnMut(Lod),
+) -> anyhow::Result<PyramidReport> {
+    let map_lods = map_type.info().lods.clone();
+    let lods = options.lods.clone().unwrap_or_else(|| map_lods.clone());
+    if lods.is_empty() {
+        bail!(
+            "no lods to build, the min lod {} is above the max lod {}",
+            lods.start(),
+            lods.end()
+        );
+    }
+    if !map_lods.contains(lods.start()) || !map_lods.contains(lods.end()) {
+        bail!(
+            "the lods {}..={} aren't all {map_type}'s, which are {}..={}",
+            lods.start(),
+            lods.end(),
+            map_lods.start(),
+            map_lods.end()
+        );
+    }
+
+    let source_modified = fs::metadata(source)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("couldn't read {}", source.display()))?;
+    let mut report = PyramidReport::default();
+
+    let max_lod = Lod::new(*lods.end());
+    // Decoding the source is slow, it's skipped while all the tiles are up to date
+    let mut source_image = None;
+    for lod in lods.rev().map(Lod::new) {
+        for x_idx in 0..lod.tiles_nb() {
+            for y_idx in 0..lod.tiles_nb() {
+                let path = assets_dir.join(map_type.tile_path(lod, x_idx, y_idx));
+                if options.skip_up_to_date && is_up_to_date(&path, source_modified) {
+                    report.skipped += 1;
+                    continue;
+                }
+
+                let tile = if lod == max_lod {
+                    let source_image = match &mut source_image {
+                        Some(source_image) => source_image,
+                        source_image @ None => source_image.insert(load_source(source, lod)?),
+                    };
+                    source_tile(source_image, lod, x_idx, y_idx, options.filter)
+                } else {
+                    let child_lod = Lod::new(lod.value() + 1);
+                    let [top_left, top_right, bottom_left, bottom_right] =
+                        child_indexes(x_idx, y_idx).map(|(x_idx, y_idx)| {
+                            read_tile(&assets_dir.join(map_type.tile_path(child_lod, x_idx, y_idx)))
+                        });
+                    merge_tiles(
+                        &[top_left?, top_right?, bottom_left?, bottom_right?],
+                        options.filter,
+                    )
+                };
+                write_jpeg(&path, &tile, options.quality)?;
+                report.written += 1;
+            }
+        }
+        on_lod_built(lod);
+    }
+
+    Ok(report)
+}
+
+/// The tile's area of a `source` image covering the whole map, resized to a tile. The source is
+/// stretched if it's not a square.
+#[allow(clippy::cast_possible_truncation)]
+#[must_use]
+pub fn source_tile(
+    source: &RgbImage,
+    lod: Lod,
+    x_idx: u32,
+    y_idx: u32,
+    filter: FilterType,
+) -> RgbImage {
+    let tiles_nb = u64::from(lod.tiles_nb());
+    // The bounds of the tiles' areas, rounded the same way for adjacent tiles, within the side
+    let bound = |idx: u32, side: u32| (u64::from(idx) * u64::from(side) / tiles_nb) as u32;
+    let (x, y) = (bound(x_idx, source.width()), bound(y_idx, source.height()));
+    let width = bound(x_idx + 1, source.width()) - x;
+    let height = bound(y_idx + 1, source.height()) - y;
+    let area = imageops::crop_imm(source, x, y, width, height);
+    if (width, height) == (TILE_SIZE_PX, TILE_SIZE_PX) {
+        return area.to_image();
+    }
+    imageops::resize(&*area, TILE_SIZE_PX, TILE_SIZE_PX, filter)
+}
+
+/// A tile from its 4 tiles at the next lod, ordered like [`child_indexes`]
+#[must_use]
+pub fn merge_tiles(children: &[RgbImage; 4], filter: FilterType) -> RgbImage {
+    let mut merged = RgbImage::new(TILE_SIZE_PX * 2, TILE_SIZE_PX * 2);
+    for (child, (x_idx, y_idx)) in children.iter().zip(child_indexes(0, 0)) {
+        imageops::replace(
+            &mut merged,
+            child,
+            i64::from(x_idx * TILE_SIZE_PX),
+            i64::from(y_idx * TILE_SIZE_PX),
+        );
+    }
+    imageops::resize(&merged, TILE_SIZE_PX, TILE_SIZE_PX, filter)
+}
+
+/// The indexes of the tile's 4 tiles at the next lod, row by row from the top left one
+#[must_use]
+pub fn child_indexes(x_idx: u32, y_idx: u32) -> [(u32, u32); 4] {
+    let (x_idx, y_idx) = (x_idx * 2, y_idx * 2);
+    [
+        (x_idx, y_idx),
+        (x_idx + 1, y_idx),
+        (x_idx, y_idx + 1),
+        (x_idx + 1, y_idx + 1),
+    ]
+}
+
+/// The source must have a pixel per pixel of `lod`'s tiles, so that they aren't upscaled
+fn load_source(source: &Path, lod: Lod) -> anyhow::Result<RgbImage> {
+    let mut reader = Reader::open(source)?.with_guessed_format()?;
+    // The sources are far bigger than the default limits allow
+    reader.no_limits();
+    let image = reader
+        .decode()
+        .with_context(|| format!("couldn't decode {}", source.display()))?
+        .into_rgb8();
+
+    let min_size = TILE_SIZE_PX * lod.tiles_nb();
+    if image.width() < min_size || image.height() < min_size {
+        bail!(
+            "the source must be at least {min_size}x{min_size} pixels for lod {lod}, it's {}x{}",
+            image.width(),
+            image.height()
+        );
+    }
+    Ok(image)
+}
+
+fn is_up_to_date(path: &Path, source_modified: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified >= source_modified)
+}
+
+fn read_tile(path: &Path) -> anyhow::Result<RgbImage> {
+    Ok(image::open(path)
+        .with_context(|| format!("couldn't read {}", path.display()))?
+        .into_rgb8())
+}
+
+fn write_jpeg(path: &Path, tile: &RgbImage, quality: u8) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::create(path).with_context(|| format!("couldn't create {}", path.display()))?;
+    JpegEncoder::new_with_quality(BufWriter::new(file), quality).encode_image(tile)?;
+    Ok(())
+}
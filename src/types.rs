@@ -1,48 +1,92 @@
-use bevy::prelude::{Vec2, Vec3};
-use serde::Deserialize;
+use bevy::{
+    prelude::{Vec2, Vec3},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::resources::Lod;
+use crate::resources::MapType;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LocationLayerIcon {
     pub url: String,
     pub width: u32,
     pub height: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LocationLayerMarker {
     #[serde(rename = "coords")]
     pub pos: Vec2,
+    /// Positions used instead of `pos` for some lods, to pull overlapping icons apart
+    #[serde(rename = "zoomAdjustedCoords", default)]
+    pub zoom_adjusted_pos: HashMap<u32, Vec2>,
     pub elv: f32,
     pub id: String,
     pub name: Option<String>,
+    /// Title of the marker's wiki page
+    pub link: Option<String>,
+    /// Trail attached to the marker (Korok seeds, travel routes...), same coordinates system as `pos`
+    #[serde(default)]
+    pub path: Vec<Vec2>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct LocationLayer {
     pub icon: Option<LocationLayerIcon>,
     pub markers: Vec<LocationLayerMarker>,
     #[serde(rename = "minZoom", default)]
     pub min_lod: u32,
+    /// Up to the map's most detailed lod when missing
     #[serde(rename = "maxZoom", default = "max_lod")]
     pub max_lod: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub name: String,
     pub source: Option<String>,
+    pub link: Option<String>,
     pub layers: Vec<LocationLayer>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Material {
     pub name: String,
     #[serde(rename = "markerCoords")]
     pub pos: Vec<Vec3>,
 }
 
+/// A pin dropped by the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pin {
+    pub id: u64,
+    pub map_type: MapType,
+    /// In world coordinates
+    pub pos: Vec2,
+    pub label: String,
+    pub note: String,
+    /// File name in `assets/icons`
+    pub icon: String,
+}
+
 fn max_lod() -> u32 {
-    Lod::MAX_VALUE
+    u32::MAX
+}
+
+/// A point measured with the ruler
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RulerPoint {
+    /// In world coordinates
+    pub pos: Vec2,
+    /// Of the marker the point snapped to, if any
+    pub elevation: Option<f32>,
+}
+
+/// A measurement saved from the ruler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: u64,
+    pub map_type: MapType,
+    pub label: String,
+    pub points: Vec<RulerPoint>,
 }
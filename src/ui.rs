@@ -5,18 +5,73 @@ use bevy::{
 };
 use bevy_egui::{egui, EguiContexts};
 
-use crate::resources::{DisplayedMarkers, FocusedMarkers, MapType, Markers, Options};
+use crate::{
+    camera::{FlyTo, MainCamera},
+    coords::{GameCoord, WorldPos},
+    pins::PINS_CATEGORY,
+    resources::{
+        Annotations, AppState, CompletedMarkers, Crosshair, DisplayedMarkers, FocusedMarkers, Lod,
+        MapType, Markers, MarkersProgress, Notifications, Options, PinEditor, PinIcons, Pins,
+        Ruler, SelectedMarker, TileCache, TILE_MEMORY_BYTES,
+    },
+    ruler::{elevation_difference, format_distance, segment_lengths, total_length},
+    search::SearchIndex,
+};
+
+const WIKI_URL: &str = "https://zeldawiki.wiki/wiki/";
 
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<EguiHoverStatus>()
+            .init_resource::<Notifications>()
             .add_systems(PreUpdate, update_egui_mouse_check)
-            .add_systems(Update, filters_ui);
+            .add_systems(
+                Update,
+                (
+                    filters_ui,
+                    marker_details_ui,
+                    pins_ui,
+                    pin_editor_ui,
+                    ruler_ui,
+                    search_ui,
+                    status_bar_ui,
+                    tile_cache_ui,
+                )
+                    .run_if(in_state(AppState::Ready)),
+            )
+            .add_systems(
+                Update,
+                (
+                    loading_ui.run_if(in_state(AppState::Loading)),
+                    loading_error_ui.run_if(in_state(AppState::Failed)),
+                    notifications_ui,
+                ),
+            );
     }
 }
 
+#[derive(Default)]
+struct SearchState {
+    query: String,
+    /// Indexes in the `SearchIndex` entries
+    results: Vec<usize>,
+}
+
+#[derive(Default)]
+struct GoToState {
+    input: String,
+    /// Why the input couldn't be parsed
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct RulerState {
+    /// Of the annotation saved from the ruler
+    label: String,
+}
+
 #[derive(Resource, Default)]
 pub struct EguiHoverStatus {
     is_hovered: bool,
@@ -53,14 +108,9 @@ fn filters_ui(
 ) {
     egui::Window::new("Levels").show(contexts.ctx_mut(), |ui| {
         for map in MapType::iter() {
-            if ui.button(map.as_str()).clicked() {
+            // Markers spawning and filtering are driven by the `MapType` change detection
+            if ui.button(map.name()).clicked() && *map_type != *map {
                 *map_type = *map;
-                displayed_markers.markers_mut().clear();
-                let locations = markers
-                    .locations(*map_type)
-                    .iter()
-                    .map(|location| location.name.clone());
-                displayed_markers.add_missing_from(locations);
             }
         }
     });
@@ -72,7 +122,7 @@ fn filters_ui(
                     .locations(*map_type)
                     .iter()
                     .map(|location| location.name.clone());
-                displayed_markers.add_missing_from(locations);
+                displayed_markers.add_missing_from(*map_type, locations);
             }
 
             if ui.button("Hide all").clicked() {
@@ -80,13 +130,18 @@ fn filters_ui(
                     .locations(*map_type)
                     .iter()
                     .map(|location| &location.name);
-                displayed_markers.remove_from(locations);
+                displayed_markers.remove_from(*map_type, locations);
+            }
+
+            let mut hide_completed = displayed_markers.hide_completed;
+            if ui.checkbox(&mut hide_completed, "Hide completed").changed() {
+                displayed_markers.hide_completed = hide_completed;
             }
 
             for location in markers.locations(*map_type) {
-                let mut checked = displayed_markers.markers().contains(&location.name);
+                let mut checked = displayed_markers.is_displayed(*map_type, &location.name);
                 if ui.checkbox(&mut checked, &location.name).changed() {
-                    displayed_markers.toggle(location.name.clone());
+                    displayed_markers.toggle(*map_type, location.name.clone());
                 }
             }
         });
@@ -99,19 +154,19 @@ fn filters_ui(
                     .materials(*map_type)
                     .iter()
                     .map(|material| &material.name);
-                displayed_markers.remove_from(materials);
+                displayed_markers.remove_from(*map_type, materials);
             }
 
             for resource in markers.materials(*map_type) {
-                let mut checked = displayed_markers.markers().contains(&resource.name);
+                let mut checked = displayed_markers.is_displayed(*map_type, &resource.name);
                 if ui.checkbox(&mut checked, &resource.name).changed() {
-                    displayed_markers.toggle(resource.name.clone());
+                    displayed_markers.toggle(*map_type, resource.name.clone());
                 }
             }
         });
     });
 
-    if let Some(focused_marker) = focused_markers.markers().first() {
+    if let Some((_, focused_marker)) = focused_markers.markers().first() {
         if let Some(cursor_position) = primary_window.single().cursor_position() {
             let pos = cursor_position + 8.0;
             egui::Window::new("")
@@ -134,3 +189,438 @@ fn filters_ui(
         }
     }
 }
+
+#[allow(clippy::needless_pass_by_value)]
+fn loading_ui(mut contexts: EguiContexts, progress: Res<MarkersProgress>) {
+    egui::Window::new("Loading the markers")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for map_type in MapType::iter() {
+                ui.label(map_type.name());
+                ui.add(egui::ProgressBar::new(progress.progress(*map_type)).show_percentage());
+            }
+        });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn loading_error_ui(mut contexts: EguiContexts, progress: Res<MarkersProgress>) {
+    egui::CentralPanel::default().show(contexts.ctx_mut(), |ui| {
+        ui.heading("The markers couldn't be loaded");
+        for error in &progress.errors {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn notifications_ui(
+    mut contexts: EguiContexts,
+    mut notifications: ResMut<Notifications>,
+    time: Res<Time>,
+) {
+    notifications.remove_expired(time.elapsed_seconds());
+    if notifications.notifications().is_empty() {
+        return;
+    }
+
+    let mut dismissed = None;
+    egui::Area::new("notifications")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10.0, -10.0))
+        .show(contexts.ctx_mut(), |ui| {
+            for (index, notification) in notifications.notifications().iter().enumerate() {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if notification.is_error {
+                            ui.colored_label(egui::Color32::LIGHT_RED, &notification.message);
+                        } else {
+                            ui.label(&notification.message);
+                        }
+                        if ui.small_button("x").clicked() {
+                            dismissed = Some(index);
+                        }
+                    });
+                });
+            }
+        });
+    if let Some(index) = dismissed {
+        notifications.dismiss(index);
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn tile_cache_ui(mut contexts: EguiContexts, options: Res<Options>, tile_cache: Res<TileCache>) {
+    if !options.debug_display {
+        return;
+    }
+
+    let stats = tile_cache.stats();
+    egui::Window::new("Tiles").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!(
+            "loaded: {}/{} + {} pinned",
+            tile_cache.len(),
+            tile_cache.max_tiles(),
+            tile_cache.pinned_len()
+        ));
+        ui.label(format!(
+            "memory: ~{} MB",
+            (tile_cache.len() + tile_cache.pinned_len()) * TILE_MEMORY_BYTES / (1024 * 1024)
+        ));
+        ui.label(format!("hits: {}", stats.hits));
+        ui.label(format!("misses: {}", stats.misses));
+        ui.label(format!("evictions: {}", stats.evictions));
+    });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn marker_details_ui(
+    mut contexts: EguiContexts,
+    mut selected_marker: ResMut<SelectedMarker>,
+    mut completed_markers: ResMut<CompletedMarkers>,
+) {
+    let Some(marker) = selected_marker.marker() else {
+        return;
+    };
+
+    let mut open = true;
+    egui::Window::new("Marker")
+        .open(&mut open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("marker_details")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Category");
+                    ui.label(&marker.category);
+                    ui.end_row();
+
+                    ui.label("Name");
+                    ui.label(marker.name.as_deref().unwrap_or("-"));
+                    ui.end_row();
+
+                    ui.label("Id");
+                    ui.label(marker.id.as_deref().unwrap_or("-"));
+                    ui.end_row();
+
+                    ui.label("Coordinates");
+                    ui.label(format!("{:.2}, {:.2}", marker.coords.x, marker.coords.z));
+                    ui.end_row();
+
+                    ui.label("Elevation");
+                    ui.label(format!("{:.2}", marker.coords.elevation));
+                    ui.end_row();
+
+                    ui.label("Source");
+                    ui.label(marker.source.as_deref().unwrap_or("-"));
+                    ui.end_row();
+
+                    ui.label("Link");
+                    ui.label(marker.link.as_deref().unwrap_or("-"));
+                    ui.end_row();
+                });
+
+            if let Some(id) = &marker.id {
+                let mut is_completed = completed_markers.is_completed(id);
+                if ui.checkbox(&mut is_completed, "Completed").changed() {
+                    completed_markers.toggle(id.clone());
+                }
+            }
+
+            if let Some(link) = &marker.link {
+                if ui.button("Open wiki page").clicked() {
+                    ui.ctx().open_url(egui::OpenUrl::new_tab(format!(
+                        "{WIKI_URL}{}",
+                        link.replace(' ', "_")
+                    )));
+                }
+            }
+        });
+
+    if !open {
+        selected_marker.clear();
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn pins_ui(
+    mut contexts: EguiContexts,
+    map_type: Res<MapType>,
+    mut displayed_markers: ResMut<DisplayedMarkers>,
+    mut pins: ResMut<Pins>,
+    mut pin_editor: ResMut<PinEditor>,
+) {
+    egui::Window::new("Pins").show(contexts.ctx_mut(), |ui| {
+        ui.label("Right click on the map to drop a pin");
+
+        let mut checked = displayed_markers.is_displayed(*map_type, PINS_CATEGORY);
+        if ui.checkbox(&mut checked, PINS_CATEGORY).changed() {
+            displayed_markers.toggle(*map_type, PINS_CATEGORY.to_string());
+        }
+
+        let mut removed_pin = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for pin in pins.pins(*map_type) {
+                ui.horizontal(|ui| {
+                    ui.label(if pin.label.is_empty() {
+                        "Unnamed pin"
+                    } else {
+                        pin.label.as_str()
+                    });
+                    if ui.small_button("Edit").clicked() {
+                        pin_editor.edit(pin.clone());
+                    }
+                    if ui.small_button("Delete").clicked() {
+                        removed_pin = Some(pin.id);
+                    }
+                });
+            }
+        });
+        if let Some(id) = removed_pin {
+            pins.remove(id);
+        }
+    });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn pin_editor_ui(
+    mut contexts: EguiContexts,
+    pin_icons: Res<PinIcons>,
+    mut pins: ResMut<Pins>,
+    mut pin_editor: ResMut<PinEditor>,
+) {
+    let Some(pin) = pin_editor.pin_mut() else {
+        return;
+    };
+
+    let mut open = true;
+    let mut saved = false;
+    let mut deleted = false;
+    egui::Window::new("Pin")
+        .open(&mut open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("pin_editor").num_columns(2).show(ui, |ui| {
+                ui.label("Label");
+                ui.text_edit_singleline(&mut pin.label);
+                ui.end_row();
+
+                ui.label("Note");
+                ui.text_edit_multiline(&mut pin.note);
+                ui.end_row();
+
+                ui.label("Icon");
+                egui::ComboBox::from_id_source("pin_icon")
+                    .selected_text(pin.icon.as_str())
+                    .show_ui(ui, |ui| {
+                        for icon in pin_icons.icons() {
+                            ui.selectable_value(&mut pin.icon, icon.clone(), icon);
+                        }
+                    });
+                ui.end_row();
+            });
+
+            ui.horizontal(|ui| {
+                saved = ui.button("Save").clicked();
+                deleted = ui.button("Delete").clicked();
+            });
+        });
+
+    if saved {
+        pins.upsert(pin.clone());
+    } else if deleted {
+        pins.remove(pin.id);
+    }
+    if saved || deleted || !open {
+        pin_editor.close();
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn ruler_ui(
+    mut contexts: EguiContexts,
+    map_type: Res<MapType>,
+    mut ruler: ResMut<Ruler>,
+    mut annotations: ResMut<Annotations>,
+    mut ruler_state: Local<RulerState>,
+) {
+    egui::Window::new("Ruler").show(contexts.ctx_mut(), |ui| {
+        let mut active = ruler.is_active();
+        if ui
+            .checkbox(&mut active, "Measure (left click on the map)")
+            .changed()
+        {
+            ruler.set_active(active);
+        }
+
+        if ruler.map_type() == Some(*map_type) && !ruler.points().is_empty() {
+            let points = ruler.points();
+            for (i, length) in segment_lengths(points, *map_type).iter().enumerate() {
+                ui.label(format!("{}. {}", i + 1, format_distance(*length)));
+            }
+            ui.label(format!(
+                "Total: {}",
+                format_distance(total_length(points, *map_type))
+            ));
+            if let Some(difference) = elevation_difference(points) {
+                ui.label(format!("Elevation: {difference:+.0} m"));
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Undo").clicked() {
+                    ruler.remove_last_point();
+                }
+                if ui.button("Clear").clicked() {
+                    ruler.clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut ruler_state.label)
+                        .hint_text("Label")
+                        .desired_width(120.0),
+                );
+                if ui.button("Save as annotation").clicked() {
+                    annotations.add(
+                        *map_type,
+                        std::mem::take(&mut ruler_state.label),
+                        ruler.points().to_vec(),
+                    );
+                    ruler.clear();
+                }
+            });
+        }
+
+        let mut removed_annotation = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for annotation in annotations.annotations(*map_type) {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} - {}",
+                        if annotation.label.is_empty() {
+                            "Unnamed annotation"
+                        } else {
+                            annotation.label.as_str()
+                        },
+                        format_distance(total_length(&annotation.points, *map_type))
+                    ));
+                    if ui.small_button("Delete").clicked() {
+                        removed_annotation = Some(annotation.id);
+                    }
+                });
+            }
+        });
+        if let Some(id) = removed_annotation {
+            annotations.remove(id);
+        }
+    });
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn search_ui(
+    mut contexts: EguiContexts,
+    search_index: Res<SearchIndex>,
+    mut displayed_markers: ResMut<DisplayedMarkers>,
+    mut fly_to: EventWriter<FlyTo>,
+    mut search_state: Local<SearchState>,
+) {
+    let search_state = &mut *search_state;
+    egui::Window::new("Search").show(contexts.ctx_mut(), |ui| {
+        let query = egui::TextEdit::singleline(&mut search_state.query)
+            .hint_text("Name or id of a marker or material");
+        // The index is rebuilt when the markers are reloaded, invalidating the results' indexes
+        if ui.add(query).changed() || search_index.is_changed() {
+            search_state.results = search_index.search(&search_state.query);
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for index in &search_state.results {
+                let Some(entry) = search_index.entries().get(*index) else {
+                    continue;
+                };
+                if ui
+                    .button(format!("{} - {}", entry.label, entry.map_type))
+                    .clicked()
+                {
+                    displayed_markers
+                        .markers_mut(entry.map_type)
+                        .insert(entry.category.clone());
+                    fly_to.send(FlyTo {
+                        map_type: entry.map_type,
+                        position: entry.position,
+                        scale: entry.lod.scale(entry.map_type),
+                    });
+                }
+            }
+        });
+    });
+}
+
+/// The cursor's game coordinates, the lod and the map, and a field to center the camera on
+/// coordinates, marked by a [`Crosshair`]
+#[allow(clippy::needless_pass_by_value, clippy::too_many_arguments)]
+fn status_bar_ui(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    lod: Res<Lod>,
+    map_type: Res<MapType>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut fly_to: EventWriter<FlyTo>,
+    time: Res<Time>,
+    mut go_to_state: Local<GoToState>,
+) {
+    let Ok((camera, camera_transform, projection)) = camera.get_single() else {
+        return;
+    };
+    let cursor = primary_window
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .map(|position| WorldPos(position).to_game(0.0));
+
+    let go_to_state = &mut *go_to_state;
+    egui::TopBottomPanel::bottom("status_bar").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label(cursor.map_or("-".to_string(), |coords| {
+                format!("{:.0}, {:.0}", coords.x, coords.z)
+            }));
+            ui.separator();
+            ui.label(format!("Lod {}", *lod));
+            ui.separator();
+            ui.label(map_type.name());
+            ui.separator();
+
+            ui.label("Go to");
+            let input = ui.add(
+                egui::TextEdit::singleline(&mut go_to_state.input)
+                    .hint_text("x, z or x, y, z")
+                    .desired_width(140.0),
+            );
+            let submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if submitted || ui.button("Go").clicked() {
+                match go_to_state.input.parse::<GameCoord>() {
+                    Ok(coords) => {
+                        go_to_state.error = None;
+                        let position = coords.to_world().0;
+                        fly_to.send(FlyTo {
+                            map_type: *map_type,
+                            position,
+                            scale: projection.scale,
+                        });
+                        commands.insert_resource(Crosshair {
+                            map_type: *map_type,
+                            position,
+                            created_at: time.elapsed_seconds(),
+                        });
+                    }
+                    Err(err) => go_to_state.error = Some(err),
+                }
+            }
+            if let Some(error) = &go_to_state.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+        });
+    });
+}
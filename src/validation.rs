@@ -0,0 +1,456 @@
+//! Validation of the markers' JSON files, used by the `totk validate-markers` subcommand.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::Vec2, utils::HashMap};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    marker_source::{MarkersFileKind, MarkersFormat},
+    resources::MapType,
+    types::{Location, Material},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    /// Doesn't prevent the markers from loading
+    Warning,
+}
+
+/// The content of one of the markers' JSON files
+#[derive(Debug)]
+pub struct MarkersFile {
+    pub map_type: MapType,
+    pub kind: MarkersFileKind,
+    pub path: PathBuf,
+    pub content: String,
+}
+
+impl MarkersFile {
+    /// The locations and materials files of every map in `markers_dir`
+    #[allow(clippy::missing_errors_doc)]
+    pub fn read_all(markers_dir: &Path) -> anyhow::Result<Vec<Self>> {
+        let mut files = Vec::new();
+        for map_type in MapType::iter() {
+            for kind in MarkersFileKind::iter() {
+                let path = markers_dir.join(MarkersFormat::Json.path(*map_type, *kind));
+                let content = std::fs::read_to_string(&path)?;
+                files.push(Self {
+                    map_type: *map_type,
+                    kind: *kind,
+                    path,
+                    content,
+                });
+            }
+        }
+        Ok(files)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarkersIssue {
+    pub severity: Severity,
+    pub file: PathBuf,
+    /// From 1, `None` when the issue isn't tied to a position in the file
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// The dot separated path of the JSON value, e.g. `3.layers.0.markers.12`
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl Display for MarkersIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{line}:{column}")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, ": {severity}: {}", self.message)?;
+        if let Some(path) = &self.path {
+            write!(f, " (at {path})")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MarkersValidation {
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<MarkersIssue>,
+}
+
+impl Display for MarkersValidation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{issue}")?;
+        }
+        writeln!(f, "{} errors, {} warnings", self.errors, self.warnings)
+    }
+}
+
+/// Where a value was first found, to point at it from its duplicates
+struct FirstSeen {
+    map_type: MapType,
+    file: PathBuf,
+    position: Option<(usize, usize)>,
+}
+
+impl MarkersValidation {
+    #[must_use]
+    pub fn validate(files: &[MarkersFile]) -> Self {
+        let mut validation = Self::default();
+        let mut ids: HashMap<String, FirstSeen> = HashMap::default();
+        // Sorted for a stable report
+        let mut categories: BTreeMap<String, Vec<FirstSeen>> = BTreeMap::new();
+
+        for file in files {
+            let mut context = FileContext {
+                file,
+                spans: None,
+                validation: &mut validation,
+            };
+            match file.kind {
+                MarkersFileKind::Locations => {
+                    let Some(locations) = context.parse::<Vec<Location>>() else {
+                        continue;
+                    };
+                    for (index, location) in locations.iter().enumerate() {
+                        context.location(index, location, &mut ids);
+                        categories
+                            .entry(location.name.clone())
+                            .or_default()
+                            .push(context.first_seen(&index.to_string()));
+                    }
+                }
+                MarkersFileKind::Materials => {
+                    let Some(materials) = context.parse::<Vec<Material>>() else {
+                        continue;
+                    };
+                    for (index, material) in materials.iter().enumerate() {
+                        context.material(index, material);
+                        categories
+                            .entry(material.name.clone())
+                            .or_default()
+                            .push(context.first_seen(&index.to_string()));
+                    }
+                }
+            }
+        }
+
+        for (name, seen) in categories {
+            // In the manifest's order, the files can be in any order
+            let map_types = MapType::iter()
+                .iter()
+                .filter(|map_type| seen.iter().any(|seen| seen.map_type == **map_type))
+                .map(|map_type| map_type.as_str())
+                .collect::<Vec<_>>();
+            if map_types.len() < 2 {
+                continue;
+            }
+            validation.push(MarkersIssue {
+                severity: Severity::Warning,
+                file: seen[0].file.clone(),
+                line: seen[0].position.map(|(line, _)| line),
+                column: seen[0].position.map(|(_, column)| column),
+                path: None,
+                message: format!(
+                    "category {name} is on several maps: {}",
+                    map_types.join(", ")
+                ),
+            });
+        }
+
+        validation
+    }
+
+    #[must_use]
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    fn push(&mut self, issue: MarkersIssue) {
+        match issue.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        self.issues.push(issue);
+    }
+}
+
+struct FileContext<'a> {
+    file: &'a MarkersFile,
+    /// Only computed once the file is parsed, so for valid JSON
+    spans: Option<JsonSpans>,
+    validation: &'a mut MarkersValidation,
+}
+
+impl FileContext<'_> {
+    fn parse<T: DeserializeOwned>(&mut self) -> Option<T> {
+        let mut deserializer = serde_json::Deserializer::from_str(&self.file.content);
+        let mut unknown_fields = Vec::new();
+        let parsed = serde_ignored::deserialize(&mut deserializer, |path| {
+            let mut segments = Vec::new();
+            path_segments(&path, &mut segments);
+            unknown_fields.push(segments.join("."));
+        })
+        .and_then(|parsed| deserializer.end().map(|()| parsed));
+
+        let parsed = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                // The position is already in the issue
+                let message = err.to_string();
+                let suffix = format!(" at line {} column {}", err.line(), err.column());
+                self.validation.push(MarkersIssue {
+                    severity: Severity::Error,
+                    file: self.file.path.clone(),
+                    line: Some(err.line()),
+                    column: Some(err.column()),
+                    path: None,
+                    message: message
+                        .strip_suffix(&suffix)
+                        .unwrap_or(&message)
+                        .to_string(),
+                });
+                return None;
+            }
+        };
+
+        self.spans = Some(JsonSpans::new(&self.file.content));
+        for path in unknown_fields {
+            let field = path.rsplit('.').next().unwrap_or_default().to_string();
+            self.issue(Severity::Warning, &path, format!("unknown field {field}"));
+        }
+        Some(parsed)
+    }
+
+    fn location(
+        &mut self,
+        index: usize,
+        location: &Location,
+        ids: &mut HashMap<String, FirstSeen>,
+    ) {
+        for (layer_index, layer) in location.layers.iter().enumerate() {
+            let layer_path = format!("{index}.layers.{layer_index}");
+            if layer.markers.is_empty() {
+                self.issue(
+                    Severity::Warning,
+                    &layer_path,
+                    format!("empty layer in {}", location.name),
+                );
+            }
+            if layer.min_lod > layer.max_lod {
+                self.issue(
+                    Severity::Error,
+                    &layer_path,
+                    format!(
+                        "minZoom {} is greater than maxZoom {}",
+                        layer.min_lod, layer.max_lod
+                    ),
+                );
+            }
+
+            for (marker_index, marker) in layer.markers.iter().enumerate() {
+                let marker_path = format!("{layer_path}.markers.{marker_index}");
+                self.coords(&format!("{marker_path}.coords"), marker.pos);
+                for (lod, pos) in &marker.zoom_adjusted_pos {
+                    self.coords(&format!("{marker_path}.zoomAdjustedCoords.{lod}"), *pos);
+                }
+                for (point_index, pos) in marker.path.iter().enumerate() {
+                    self.coords(&format!("{marker_path}.path.{point_index}"), *pos);
+                }
+
+                let seen = self.first_seen(&marker_path);
+                if let Some(first) = ids.get(&marker.id) {
+                    let at = first
+                        .position
+                        .map_or_else(String::new, |(line, _)| format!(":{line}"));
+                    let message = format!(
+                        "duplicate id {}, first at {}{at}",
+                        marker.id,
+                        first.file.display()
+                    );
+                    self.issue(Severity::Error, &marker_path, message);
+                } else {
+                    ids.insert(marker.id.clone(), seen);
+                }
+            }
+        }
+    }
+
+    fn material(&mut self, index: usize, material: &Material) {
+        for (pos_index, pos) in material.pos.iter().enumerate() {
+            self.coords(&format!("{index}.markerCoords.{pos_index}"), pos.truncate());
+        }
+    }
+
+    fn coords(&mut self, path: &str, pos: Vec2) {
+        let half_size = self.file.map_type.size_px() / 2.0;
+        if pos.abs().max_element() > half_size {
+            self.issue(
+                Severity::Error,
+                path,
+                format!(
+                    "coordinates [{}, {}] are outside of the map, from -{half_size} to {half_size}",
+                    pos.x, pos.y
+                ),
+            );
+        }
+    }
+
+    fn first_seen(&self, path: &str) -> FirstSeen {
+        FirstSeen {
+            map_type: self.file.map_type,
+            file: self.file.path.clone(),
+            position: self.position(path),
+        }
+    }
+
+    fn position(&self, path: &str) -> Option<(usize, usize)> {
+        self.spans.as_ref().and_then(|spans| spans.position(path))
+    }
+
+    fn issue(&mut self, severity: Severity, path: &str, message: String) {
+        let position = self.position(path);
+        self.validation.push(MarkersIssue {
+            severity,
+            file: self.file.path.clone(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+            path: Some(path.to_string()),
+            message,
+        });
+    }
+}
+
+fn path_segments(path: &serde_ignored::Path, segments: &mut Vec<String>) {
+    match path {
+        serde_ignored::Path::Root => {}
+        serde_ignored::Path::Seq { parent, index } => {
+            path_segments(parent, segments);
+            segments.push(index.to_string());
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            path_segments(parent, segments);
+            segments.push(key.clone());
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => path_segments(parent, segments),
+    }
+}
+
+/// The positions of the objects' members and of the arrays' objects and arrays of a valid JSON
+/// document, by their dot separated path
+struct JsonSpans {
+    offsets: HashMap<String, usize>,
+    line_starts: Vec<usize>,
+}
+
+impl JsonSpans {
+    fn new(content: &str) -> Self {
+        let mut spans = Self {
+            offsets: HashMap::default(),
+            line_starts: std::iter::once(0)
+                .chain(content.match_indices('\n').map(|(offset, _)| offset + 1))
+                .collect(),
+        };
+        let mut path = Vec::new();
+        spans.value(content.as_bytes(), 0, &mut path);
+        spans
+    }
+
+    /// The line and column, from 1, of the value at `path`
+    fn position(&self, path: &str) -> Option<(usize, usize)> {
+        let offset = *self.offsets.get(path)?;
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        Some((line, offset - self.line_starts[line - 1] + 1))
+    }
+
+    fn record(&mut self, path: &[String], offset: usize) {
+        self.offsets.insert(path.join("."), offset);
+    }
+
+    /// Returns the offset after the value starting at, or after whitespaces from, `offset`
+    fn value(&mut self, content: &[u8], offset: usize, path: &mut Vec<String>) -> usize {
+        let mut offset = skip_whitespaces(content, offset);
+        match content.get(offset) {
+            Some(b'{') => {
+                offset = skip_whitespaces(content, offset + 1);
+                while content.get(offset) == Some(&b'"') {
+                    let key_start = offset;
+                    offset = skip_string(content, offset);
+                    path.push(String::from_utf8_lossy(&content[key_start + 1..offset - 1]).into());
+                    self.record(path, key_start);
+                    // The colon
+                    offset = skip_whitespaces(content, offset) + 1;
+                    offset = self.value(content, offset, path);
+                    path.pop();
+                    offset = skip_whitespaces(content, offset);
+                    if content.get(offset) == Some(&b',') {
+                        offset = skip_whitespaces(content, offset + 1);
+                    }
+                }
+                offset + 1
+            }
+            Some(b'[') => {
+                offset = skip_whitespaces(content, offset + 1);
+                let mut index = 0;
+                while content.get(offset).is_some_and(|byte| *byte != b']') {
+                    path.push(index.to_string());
+                    if matches!(content.get(offset), Some(b'{' | b'[')) {
+                        self.record(path, offset);
+                    }
+                    offset = self.value(content, offset, path);
+                    path.pop();
+                    index += 1;
+                    offset = skip_whitespaces(content, offset);
+                    if content.get(offset) == Some(&b',') {
+                        offset = skip_whitespaces(content, offset + 1);
+                    }
+                }
+                offset + 1
+            }
+            Some(b'"') => skip_string(content, offset),
+            _ => {
+                while content.get(offset).is_some_and(|byte| {
+                    !matches!(byte, b',' | b']' | b'}') && !byte.is_ascii_whitespace()
+                }) {
+                    offset += 1;
+                }
+                offset
+            }
+        }
+    }
+}
+
+fn skip_whitespaces(content: &[u8], mut offset: usize) -> usize {
+    while content.get(offset).is_some_and(u8::is_ascii_whitespace) {
+        offset += 1;
+    }
+    offset
+}
+
+/// Returns the offset after the string starting at `offset`
+fn skip_string(content: &[u8], mut offset: usize) -> usize {
+    offset += 1;
+    while let Some(byte) = content.get(offset) {
+        match byte {
+            b'\\' => offset += 2,
+            b'"' => return offset + 1,
+            _ => offset += 1,
+        }
+    }
+    offset
+}
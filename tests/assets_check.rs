@@ -0,0 +1,181 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use futures_lite::future::block_on;
+use image::RgbImage;
+use totk_map::{
+    assets_check::{AssetProblem, AssetsReport},
+    marker_source::DirMarkerSource,
+    markers::completed_icon_path,
+    resources::{Lod, MapType, Markers, TileFormat, TileLayout, TileLayouts},
+    types::{Location, LocationLayer, LocationLayerIcon},
+};
+
+use common::TempDir;
+
+fn write_tile(assets_dir: &Path, lod: u32, x_idx: u32, y_idx: u32, size: u32) {
+    let path = assets_dir.join(MapType::SKY.tile_path(Lod::new(lod), x_idx, y_idx));
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    RgbImage::new(size, size).save(path).unwrap();
+}
+
+/// The format is detected from the tiles
+fn detected_layout(template: &str) -> TileLayout {
+    TileLayout {
+        template: template.to_string(),
+        format: None,
+    }
+}
+
+#[test]
+fn the_shipped_assets_are_complete() {
+    let markers = block_on(Markers::load(&DirMarkerSource::default())).unwrap();
+    let report = AssetsReport::check(Path::new("assets"), &markers, &TileLayouts::default());
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(report.tiles.len(), MapType::iter().len());
+}
+
+#[test]
+fn reports_the_missing_extra_and_inconsistent_tiles() {
+    let assets_dir = TempDir::new("check");
+    for lod in MapType::SKY.lods().map(Lod::value) {
+        let tiles_nb = Lod::new(lod).tiles_nb();
+        for x_idx in 0..tiles_nb {
+            for y_idx in 0..tiles_nb {
+                write_tile(&assets_dir, lod, x_idx, y_idx, 2);
+            }
+        }
+    }
+    fs::remove_file(assets_dir.join(MapType::SKY.tile_path(Lod::new(2), 1, 3))).unwrap();
+    write_tile(&assets_dir, 3, 8, 0, 2);
+    write_tile(&assets_dir, 4, 5, 5, 3);
+
+    let layout = detected_layout(TileLayout::DEFAULT_TEMPLATE);
+    let mut report = AssetsReport::default();
+    report.check_tiles(&assets_dir, MapType::SKY, &layout);
+    report.check_tiles(&assets_dir, MapType::DEPTHS, &layout);
+    let problems = report
+        .problems
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let tile = |lod, x_idx, y_idx| {
+        assets_dir
+            .join(MapType::SKY.tile_path(Lod::new(lod), x_idx, y_idx))
+            .display()
+            .to_string()
+    };
+    assert_eq!(
+        problems,
+        vec![
+            "sky lod 2 has 15 tiles instead of 16".to_string(),
+            format!("missing tile {}", tile(2, 1, 3)),
+            "sky lod 3 has 65 tiles instead of 64".to_string(),
+            format!("tile {} is 3x3 instead of 2x2", tile(4, 5, 5)),
+            "no tiles found for depths".to_string(),
+        ]
+    );
+    assert_eq!(report.tiles[0].size, Some((2, 2)));
+}
+
+#[test]
+fn the_tiles_are_checked_in_their_layout() {
+    let assets_dir = TempDir::new("check_layout");
+    let layout = detected_layout(TileLayout::XYZ_TEMPLATE);
+    for lod in MapType::SKY.lods() {
+        for x_idx in 0..lod.tiles_nb() {
+            for y_idx in 0..lod.tiles_nb() {
+                let path = assets_dir.join(layout.path_with_format(
+                    TileFormat::Png,
+                    MapType::SKY,
+                    lod,
+                    x_idx,
+                    y_idx,
+                ));
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                RgbImage::new(2, 2).save(path).unwrap();
+            }
+        }
+    }
+
+    let mut report = AssetsReport::default();
+    report.check_tiles(&assets_dir, MapType::SKY, &layout);
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(report.tiles[0].format, Some("png"));
+
+    let mut report = AssetsReport::default();
+    report.check_tiles(
+        &assets_dir,
+        MapType::SKY,
+        &detected_layout(TileLayout::DEFAULT_TEMPLATE),
+    );
+    assert_eq!(report.problems[0].to_string(), "no tiles found for sky");
+}
+
+/// The missing icons' paths, with their first user
+fn missing_icons(report: &AssetsReport) -> Vec<(String, &str)> {
+    report
+        .problems
+        .iter()
+        .map(|problem| match problem {
+            AssetProblem::MissingIcon { path, used_by } => {
+                (path.display().to_string(), used_by.as_str())
+            }
+            _ => panic!("unexpected problem {problem}"),
+        })
+        .collect()
+}
+
+#[test]
+fn reports_the_missing_icons() {
+    let layer = |url: &str| LocationLayer {
+        icon: Some(LocationLayerIcon {
+            url: url.to_string(),
+            width: 32,
+            height: 32,
+        }),
+        markers: Vec::new(),
+        min_lod: 0,
+        max_lod: u32::MAX,
+    };
+    let mut markers = Markers::default();
+    *markers.locations_mut(MapType::SURFACE) = vec![Location {
+        name: "Shrines".to_string(),
+        source: None,
+        link: None,
+        layers: vec![layer("shrine.png"), layer("unknown.png")],
+    }];
+
+    let mut report = AssetsReport::default();
+    report.check_icons(Path::new("assets"), &markers);
+    assert_eq!(
+        missing_icons(&report),
+        vec![("assets/icons/unknown.png".to_string(), "surface Shrines"),]
+    );
+}
+
+#[test]
+fn the_default_icons_are_always_checked() {
+    let assets_dir = TempDir::new("default_icons");
+    let mut report = AssetsReport::default();
+    report.check_icons(&assets_dir, &Markers::default());
+    assert_eq!(report.icons, 2);
+    let icon_path = |name: &str| assets_dir.join("icons").join(name).display().to_string();
+    assert_eq!(
+        missing_icons(&report),
+        vec![
+            (icon_path("mainquest.png"), "locations"),
+            (icon_path("star.png"), "materials"),
+        ]
+    );
+}
+
+#[test]
+fn completed_icons_are_the_r_variants_of_the_pngs() {
+    assert_eq!(
+        completed_icon_path("icons/png.icons/shrine.png"),
+        Some("icons/png.icons/shrine_r.png".to_string())
+    );
+    assert_eq!(completed_icon_path("icons/shrine.svg"), None);
+}
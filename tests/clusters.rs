@@ -0,0 +1,53 @@
+use bevy::prelude::Vec2;
+use totk_map::{
+    clusters::{cell_size, grid_clusters},
+    resources::{Lod, MapType},
+};
+
+#[test]
+fn close_markers_are_clustered() {
+    let lod = Lod::new(1);
+    let size = cell_size(MapType::SURFACE, lod);
+    let positions = [
+        Vec2::new(0.1, 0.1) * size,
+        Vec2::new(0.9, 0.5) * size,
+        Vec2::new(0.5, 0.9) * size,
+        // Alone in its cell
+        Vec2::new(2.5, 2.5) * size,
+        Vec2::new(-0.5, 0.5) * size,
+        Vec2::new(-0.1, 0.9) * size,
+    ];
+
+    let clusters = grid_clusters(&positions, MapType::SURFACE, lod);
+
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters[0].members, vec![0, 1, 2]);
+    assert_eq!(clusters[0].center, Vec2::new(0.5, 0.5) * size);
+    assert_eq!(clusters[0].bounds.min, Vec2::new(0.1, 0.1) * size);
+    assert_eq!(clusters[0].bounds.max, Vec2::new(0.9, 0.9) * size);
+    assert_eq!(clusters[1].members, vec![4, 5]);
+}
+
+#[test]
+fn clusters_split_as_the_lod_increases() {
+    let positions = [Vec2::new(10.0, 10.0), Vec2::new(600.0, 10.0)];
+
+    assert_eq!(
+        grid_clusters(&positions, MapType::SURFACE, Lod::new(0)).len(),
+        1
+    );
+    assert_eq!(
+        grid_clusters(&positions, MapType::SURFACE, Lod::new(1)).len(),
+        1
+    );
+    assert!(grid_clusters(&positions, MapType::SURFACE, Lod::new(2)).is_empty());
+}
+
+#[test]
+fn cells_are_nested() {
+    let map_type = MapType::SURFACE;
+    for lod in map_type.lods().skip(1) {
+        let parent = Lod::new(lod.value() - 1);
+        assert_eq!(cell_size(map_type, parent), cell_size(map_type, lod) * 2.0);
+    }
+}
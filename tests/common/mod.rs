@@ -0,0 +1,48 @@
+//! Fixtures shared by the integration tests, each test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// A location with two markers
+pub const LOCATIONS: &str = r#"[{
+    "name": "Shrine",
+    "layers": [{
+        "icon": { "url": "icons/shrine.png", "width": 32, "height": 32 },
+        "markers": [
+            { "id": "1", "coords": [100.0, -200.0], "elv": 12.0 },
+            { "id": "2", "coords": [300.0, -400.0], "elv": 5.0 }
+        ]
+    }]
+}]"#;
+
+/// An empty directory in the system's temporary directory, removed when dropped, even if the test
+/// panicked
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` must be unique among the tests, which run concurrently
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("totk-map-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
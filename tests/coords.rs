@@ -0,0 +1,133 @@
+use bevy::prelude::{Vec2, Vec3};
+use proptest::prelude::*;
+use totk_map::{
+    coords::{tile_bounds, GameCoord, WorldPos},
+    maps::tile_ranges,
+    resources::{Lod, MapType},
+};
+
+/// The surface's half size, the positions on the map
+const HALF_SIZE: f32 = 6_000.0;
+/// Rounding errors at the tiles' edges
+const EPSILON: f32 = 0.01;
+
+fn game_coord() -> impl Strategy<Value = GameCoord> {
+    (
+        -HALF_SIZE..HALF_SIZE,
+        -HALF_SIZE..HALF_SIZE,
+        -1_000.0_f32..1_000.0,
+    )
+        .prop_map(|(x, z, elevation)| GameCoord::new(x, z, elevation))
+}
+
+fn lod() -> impl Strategy<Value = Lod> {
+    let lods = MapType::SURFACE.info().lods.clone();
+    lods.prop_map(Lod::new)
+}
+
+#[test]
+fn json_coords_are_z_then_x() {
+    // Lookout Landing
+    let coords = GameCoord::from_json(Vec2::new(101.6, -254.1), 18.4);
+    assert_eq!(coords, GameCoord::new(-254.1, 101.6, 18.4));
+    assert_eq!(
+        GameCoord::from_material_pos(Vec3::new(101.6, -254.1, 18.4)),
+        coords
+    );
+    assert_eq!(coords.to_world(), WorldPos(Vec2::new(-254.1, 101.6)));
+    assert_eq!(
+        WorldPos::from_json(Vec2::new(101.6, -254.1)),
+        coords.to_world()
+    );
+}
+
+#[test]
+fn the_map_corners_are_in_the_corner_tiles() {
+    let lod = MapType::SURFACE.max_lod();
+    let last_idx = lod.tiles_nb() - 1;
+    let tile = |x, z| {
+        let key = GameCoord::new(x, z, 0.0)
+            .to_world()
+            .tile(MapType::SURFACE, lod);
+        (key.x_idx, key.y_idx)
+    };
+    assert_eq!(tile(-HALF_SIZE, HALF_SIZE), (0, 0));
+    assert_eq!(tile(HALF_SIZE, -HALF_SIZE), (last_idx, last_idx));
+    // Outside of the map
+    assert_eq!(tile(-2.0 * HALF_SIZE, -2.0 * HALF_SIZE), (0, last_idx));
+}
+
+#[test]
+fn coordinates_are_parsed_as_exchanged_by_the_players() {
+    let parse = |s: &str| s.parse::<GameCoord>();
+    assert_eq!(parse("-254, 101"), Ok(GameCoord::new(-254.0, 101.0, 0.0)));
+    assert_eq!(
+        parse("-254.1 18.4 101.6"),
+        Ok(GameCoord::new(-254.1, 101.6, 18.4))
+    );
+    assert_eq!(
+        parse(" -254 ,101, "),
+        Ok(GameCoord::new(-254.0, 101.0, 0.0))
+    );
+    assert_eq!(
+        parse("-254"),
+        Err("expected x, z or x, y, z, found 1 values".to_string())
+    );
+    assert_eq!(
+        parse("-254, north"),
+        Err("north isn't a number".to_string())
+    );
+    assert!(parse("NaN, 0").is_err());
+}
+
+proptest! {
+    #[test]
+    fn game_and_world_coordinates_round_trip(coords in game_coord()) {
+        prop_assert_eq!(coords.to_world().to_game(coords.elevation), coords);
+        prop_assert_eq!(
+            GameCoord::from_json(coords.json_coords(), coords.elevation),
+            coords
+        );
+    }
+
+    #[test]
+    fn formatted_coordinates_are_parsed_back(coords in game_coord()) {
+        let formatted = format!("{}, {}, {}", coords.x, coords.elevation, coords.z);
+        prop_assert_eq!(formatted.parse::<GameCoord>(), Ok(coords));
+    }
+
+    #[test]
+    fn markers_are_in_their_tile(coords in game_coord(), lod in lod()) {
+        let pos = coords.to_world();
+        let key = pos.tile(MapType::SURFACE, lod);
+        prop_assert!(
+            tile_bounds(key).inset(EPSILON).contains(pos.0),
+            "{pos:?} isn't in {key:?}"
+        );
+        prop_assert_eq!(WorldPos(key.center()).tile(MapType::SURFACE, lod), key);
+    }
+
+    #[test]
+    fn parent_tiles_contain_their_children(coords in game_coord(), lod in lod()) {
+        prop_assume!(lod > MapType::SURFACE.min_lod());
+        let pos = coords.to_world();
+        let parent = Lod::new(lod.value() - 1);
+        prop_assert_eq!(
+            pos.tile(MapType::SURFACE, lod).parent(),
+            Some(pos.tile(MapType::SURFACE, parent))
+        );
+    }
+
+    #[test]
+    fn tile_ranges_contain_the_markers_in_the_area(
+        coords in game_coord(),
+        size in 1.0_f32..2_000.0,
+        lod in lod(),
+    ) {
+        let pos = coords.to_world();
+        let key = pos.tile(MapType::SURFACE, lod);
+        let area = bevy::math::Rect::from_center_size(pos.0, Vec2::splat(size));
+        let (xs, ys) = tile_ranges(MapType::SURFACE, lod, area);
+        prop_assert!(xs.contains(&key.x_idx) && ys.contains(&key.y_idx));
+    }
+}
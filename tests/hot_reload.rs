@@ -0,0 +1,121 @@
+mod common;
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use bevy::prelude::*;
+use common::{TempDir, LOCATIONS};
+use totk_map::{
+    hot_reload::MarkersHotReloadPlugin,
+    marker_source::{DirMarkerSource, EmbeddedMarkerSource, MarkersFormat},
+    markers::MarkerSprite,
+    resources::{
+        AppState, DisplayedMarkers, FocusedMarkers, MapType, Markers, Notifications, SpawnedMarkers,
+    },
+    spatial::MarkersIndex,
+};
+
+fn markers_dir(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    fs::create_dir_all(dir.join("sky")).unwrap();
+    dir
+}
+
+/// The markers systems' resources, with the sky markers spawned from empty files
+fn app(dir: PathBuf) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .add_asset::<Image>()
+        .add_state::<AppState>()
+        .init_resource::<Markers>()
+        .init_resource::<MarkersIndex>()
+        .init_resource::<FocusedMarkers>()
+        .init_resource::<SpawnedMarkers>()
+        .init_resource::<DisplayedMarkers>()
+        .add_plugins(MarkersHotReloadPlugin { dir });
+    app.world
+        .resource_mut::<SpawnedMarkers>()
+        .mark_spawned(MapType::SKY);
+    app.world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Ready);
+    app
+}
+
+/// Updates the app until `done` or a few seconds passed
+fn update_until(app: &mut App, done: impl Fn(&mut App) -> bool) {
+    for _ in 0..500 {
+        app.update();
+        if done(app) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("timed out");
+}
+
+fn notifications(app: &App) -> Vec<(String, bool)> {
+    app.world
+        .resource::<Notifications>()
+        .notifications()
+        .iter()
+        .map(|notification| (notification.message.clone(), notification.is_error))
+        .collect()
+}
+
+#[test]
+fn the_edited_files_are_reloaded_and_their_markers_respawned() {
+    let dir = markers_dir("reload");
+    let mut app = app(dir.to_path_buf());
+    app.update();
+
+    fs::write(dir.join("sky/locations.json"), LOCATIONS).unwrap();
+    update_until(&mut app, |app| {
+        app.world
+            .resource::<Markers>()
+            .locations(MapType::SKY)
+            .len()
+            == 1
+    });
+    app.update();
+    let mut sprites = app.world.query::<&MarkerSprite>();
+    assert_eq!(sprites.iter(&app.world).count(), 2);
+    assert!(app
+        .world
+        .resource::<MarkersIndex>()
+        .get(MapType::SKY)
+        .is_some());
+    assert_eq!(notifications(&app).len(), 1);
+    assert!(!notifications(&app)[0].1);
+    // The added location is displayed
+    assert!(app
+        .world
+        .resource::<DisplayedMarkers>()
+        .is_displayed(MapType::SKY, "Shrine"));
+
+    fs::write(dir.join("sky/locations.json"), "[{").unwrap();
+    update_until(&mut app, |app| {
+        notifications(app).iter().any(|(_, is_error)| *is_error)
+    });
+    // The previous markers are kept
+    assert_eq!(
+        app.world.resource::<Markers>().locations(MapType::SKY)[0].layers[0]
+            .markers
+            .len(),
+        2
+    );
+    assert_eq!(sprites.iter(&app.world).count(), 2);
+}
+
+#[test]
+fn only_the_json_dirs_are_watched() {
+    let source = DirMarkerSource::new("mod/markers");
+    let plugin = MarkersHotReloadPlugin::for_source(&source).unwrap();
+    assert_eq!(plugin.dir, PathBuf::from("mod/markers"));
+
+    let binary_source = DirMarkerSource {
+        format: MarkersFormat::Binary,
+        ..source
+    };
+    assert!(MarkersHotReloadPlugin::for_source(&binary_source).is_none());
+    assert!(MarkersHotReloadPlugin::for_source(&EmbeddedMarkerSource::bundled()).is_none());
+}
@@ -0,0 +1,193 @@
+use std::{path::PathBuf, sync::Once};
+
+use bevy::prelude::Vec2;
+use totk_map::{
+    clusters::cluster_lods,
+    maps::tile_z,
+    marker_source::{MarkersFileKind, MarkersFormat},
+    markers::MARKERS_Z,
+    registry::MapRegistry,
+    resources::{Lod, MapType, TileKey},
+};
+
+const HYRULE: &str = r#"{
+    "id": "hyrule",
+    "name": "Hyrule",
+    "tile_root": "botw/tiles",
+    "size_px": 6000.0,
+    "min_lod": 1,
+    "max_lod": 4,
+    "markers": {
+        "locations": "botw/locations.json",
+        "materials": "botw/materials.json"
+    }
+}"#;
+
+/// A single lod, too detailed to cluster its materials
+const PLATEAU: &str = r#"{
+    "id": "plateau",
+    "name": "Great Plateau",
+    "tile_root": "botw/plateau",
+    "size_px": 1500.0,
+    "min_lod": 3,
+    "max_lod": 3,
+    "markers": {
+        "locations": "botw/plateau/locations.json",
+        "materials": "botw/plateau/materials.json"
+    }
+}"#;
+
+/// More lods than the markers' z leaves room for at 10 per lod
+const DEEP: &str = r#"{
+    "id": "deep",
+    "name": "Deep",
+    "tile_root": "deep/tiles",
+    "size_px": 2000000.0,
+    "min_lod": 0,
+    "max_lod": 12,
+    "markers": {
+        "locations": "deep/locations.json",
+        "materials": "deep/materials.json"
+    }
+}"#;
+
+/// The bundled manifest with smaller maps, installed once for all the tests
+fn install_registry() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let manifest = include_str!("../assets/maps.json");
+        let manifest = manifest.replacen(
+            r#""maps": ["#,
+            &format!(r#""maps": [{HYRULE},{PLATEAU},{DEEP},"#),
+            1,
+        );
+        MapRegistry::from_json(&manifest)
+            .unwrap()
+            .install()
+            .unwrap();
+    });
+}
+
+fn hyrule() -> MapType {
+    install_registry();
+    "hyrule".parse().unwrap()
+}
+
+fn plateau() -> MapType {
+    install_registry();
+    "plateau".parse().unwrap()
+}
+
+#[test]
+fn the_bundled_manifest_has_the_bundled_maps() {
+    let registry = MapRegistry::bundled();
+    assert_eq!(
+        registry.map_types(),
+        [MapType::SKY, MapType::SURFACE, MapType::DEPTHS]
+    );
+    assert_eq!(registry.default_map(), MapType::SURFACE);
+    assert_eq!(registry.get("depths").unwrap().name, "Depths");
+}
+
+#[test]
+fn invalid_manifests_are_rejected() {
+    let manifest = |default: &str, maps: &[&str]| {
+        format!(
+            r#"{{"default": "{default}", "maps": [{}]}}"#,
+            maps.join(",")
+        )
+    };
+    let error = |json: String| MapRegistry::from_json(&json).unwrap_err().to_string();
+
+    assert_eq!(error(manifest("hyrule", &[])), "no maps");
+    assert_eq!(
+        error(manifest("hyrule", &[HYRULE, HYRULE])),
+        "duplicate map hyrule"
+    );
+    assert_eq!(error(manifest("sky", &[HYRULE])), "unknown default map sky");
+    assert_eq!(
+        error(manifest(
+            "hyrule",
+            &[&HYRULE.replace(r#""min_lod": 1"#, r#""min_lod": 5"#)]
+        )),
+        "map hyrule: min_lod is greater than max_lod"
+    );
+    assert!(MapRegistry::from_json(&manifest("hyrule", &[HYRULE])).is_ok());
+}
+
+#[test]
+fn maps_are_parsed_and_serialized_by_id() {
+    let hyrule = hyrule();
+    assert_eq!(hyrule.name(), "Hyrule");
+    assert_eq!(MapType::iter().len(), 6);
+    assert!("lorule".parse::<MapType>().is_err());
+
+    assert_eq!(serde_json::to_string(&hyrule).unwrap(), r#""hyrule""#);
+    assert_eq!(
+        serde_json::from_str::<MapType>(r#""hyrule""#).unwrap(),
+        hyrule
+    );
+    assert!(serde_json::from_str::<MapType>(r#""lorule""#).is_err());
+}
+
+#[test]
+fn lods_and_tiles_follow_the_map() {
+    let hyrule = hyrule();
+    assert_eq!(
+        hyrule.lods().collect::<Vec<_>>(),
+        (1..=4).map(Lod::new).collect::<Vec<_>>()
+    );
+    assert_eq!(Lod::from_scale(1.0, hyrule), Lod::new(4));
+    assert_eq!(Lod::from_scale(1_000.0, hyrule), Lod::new(1));
+    assert_eq!(Lod::from_scale(1.0, MapType::SURFACE), Lod::new(6));
+    assert_eq!(
+        Lod::new(2).scale(hyrule),
+        Lod::new(4).scale(MapType::SURFACE)
+    );
+
+    assert_eq!(Lod::new(1).tile_px_size(hyrule), 3_000.0);
+    let key = TileKey {
+        map_type: hyrule,
+        lod: Lod::new(2),
+        x_idx: 3,
+        y_idx: 0,
+    };
+    assert_eq!(key.center(), Vec2::new(2_250.0, 2_250.0));
+    let parent = key.parent().unwrap();
+    assert_eq!(
+        (parent.lod, parent.x_idx, parent.y_idx),
+        (Lod::new(1), 1, 0)
+    );
+    assert_eq!(parent.parent(), None);
+
+    assert_eq!(
+        hyrule.tile_path(Lod::new(2), 3, 0),
+        PathBuf::from("botw/tiles/2/3_0.jpg")
+    );
+    assert_eq!(
+        MarkersFormat::Binary.path(hyrule, MarkersFileKind::Materials),
+        PathBuf::from("botw/materials.bin")
+    );
+}
+
+#[test]
+fn materials_are_clustered_below_the_most_detailed_lod() {
+    assert_eq!(cluster_lods(hyrule()), 1..3);
+    assert_eq!(cluster_lods(MapType::SURFACE), 0..3);
+    assert!(cluster_lods(plateau()).is_empty());
+}
+
+#[test]
+fn tiles_are_drawn_under_the_markers() {
+    install_registry();
+    let deep = "deep".parse::<MapType>().unwrap();
+    for map_type in [deep, hyrule(), plateau(), MapType::SURFACE] {
+        let tiles_z = map_type
+            .lods()
+            .map(|lod| tile_z(map_type, lod))
+            .collect::<Vec<_>>();
+        assert_eq!(tiles_z[0], 0.0);
+        assert!(tiles_z.windows(2).all(|z| z[0] < z[1]), "{tiles_z:?}");
+        assert!(tiles_z.iter().all(|z| *z < MARKERS_Z), "{tiles_z:?}");
+    }
+}
@@ -0,0 +1,81 @@
+use futures_lite::future::block_on;
+use totk_map::{
+    marker_binary::{decode, encode, is_binary, Compression},
+    marker_source::{DirMarkerSource, EmbeddedMarkerSource, MarkersFileKind, MarkersFormat},
+    resources::{MapType, Markers},
+    types::{Location, Material},
+};
+
+fn json_markers() -> Markers {
+    block_on(Markers::load(&DirMarkerSource::default())).unwrap()
+}
+
+/// The JSON files encoded in memory
+fn binary_source(markers: &Markers, compression: Compression) -> EmbeddedMarkerSource {
+    let mut source = EmbeddedMarkerSource::default();
+    for map_type in MapType::iter() {
+        source
+            .insert(
+                *map_type,
+                MarkersFileKind::Locations,
+                encode(markers.locations(*map_type), compression).unwrap(),
+            )
+            .insert(
+                *map_type,
+                MarkersFileKind::Materials,
+                encode(markers.materials(*map_type), compression).unwrap(),
+            );
+    }
+    source
+}
+
+#[test]
+fn the_binary_markers_round_trip() {
+    let markers = json_markers();
+    for compression in [Compression::None, Compression::Zstd] {
+        let source = binary_source(&markers, compression);
+        assert_eq!(
+            block_on(Markers::load(&source)).unwrap(),
+            markers,
+            "{compression:?}"
+        );
+    }
+}
+
+#[test]
+fn the_compressed_markers_are_smaller() {
+    let markers = json_markers();
+    let locations = markers.locations(MapType::SURFACE);
+    let json = serde_json::to_vec(locations).unwrap();
+    let binary = encode(locations, Compression::None).unwrap();
+    let compressed = encode(locations, Compression::Zstd).unwrap();
+    assert!(is_binary(&binary) && is_binary(&compressed));
+    assert!(!is_binary(&json));
+    assert!(binary.len() < json.len());
+    assert!(compressed.len() < binary.len() / 2);
+}
+
+#[test]
+fn invalid_binary_markers_are_errors() {
+    let binary = encode(&Vec::<Material>::new(), Compression::None).unwrap();
+    let mut other_version = binary.clone();
+    other_version[4] += 1;
+    let err = decode::<Vec<Material>>(&other_version).unwrap_err();
+    assert!(format!("{err}").contains("compiled again"), "{err}");
+
+    assert!(decode::<Vec<Location>>(&binary[..5]).is_err());
+    assert!(decode::<Vec<Location>>(b"[]").is_err());
+}
+
+#[test]
+fn the_shipped_binary_markers_are_up_to_date() {
+    let source = DirMarkerSource {
+        format: MarkersFormat::Binary,
+        ..DirMarkerSource::default()
+    };
+    assert_eq!(
+        block_on(Markers::load(&source)).unwrap(),
+        json_markers(),
+        "run `totk compile-markers --zstd`"
+    );
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::LOCATIONS;
+use futures_lite::future::block_on;
+use totk_map::{
+    marker_source::{DirMarkerSource, EmbeddedMarkerSource, MarkersFileKind},
+    markers::MarkersPlugin,
+    resources::{AppState, MapType, Markers, MarkersProgress, Ruler},
+    ui::EguiHoverStatus,
+};
+
+fn embedded_fixture() -> EmbeddedMarkerSource {
+    let mut source = EmbeddedMarkerSource::default();
+    for map_type in MapType::iter() {
+        source
+            .insert(*map_type, MarkersFileKind::Locations, LOCATIONS.as_bytes())
+            .insert(*map_type, MarkersFileKind::Materials, b"[]".as_slice());
+    }
+    source
+}
+
+#[test]
+fn loads_the_markers_from_memory() {
+    let markers = block_on(Markers::load(&embedded_fixture())).unwrap();
+    for map_type in MapType::iter() {
+        assert_eq!(markers.locations(*map_type).len(), 1);
+        assert!(markers.materials(*map_type).is_empty());
+    }
+    assert!(markers.location_marker(MapType::DEPTHS, "1").is_some());
+}
+
+#[test]
+fn missing_and_invalid_files_are_errors() {
+    let mut source = embedded_fixture();
+    source.insert(
+        MapType::SURFACE,
+        MarkersFileKind::Materials,
+        b"{".as_slice(),
+    );
+    let err = block_on(Markers::load(&source)).unwrap_err();
+    assert!(format!("{err}").contains("surface materials"), "{err}");
+
+    let err = block_on(Markers::load(&EmbeddedMarkerSource::default())).unwrap_err();
+    assert!(format!("{err}").contains("sky locations"), "{err}");
+
+    let err = block_on(Markers::load(&DirMarkerSource::new("missing"))).unwrap_err();
+    assert!(format!("{err}").contains("missing"), "{err}");
+}
+
+#[test]
+fn the_bundled_markers_are_the_assets_ones() {
+    assert_eq!(
+        block_on(Markers::load(&EmbeddedMarkerSource::bundled())).unwrap(),
+        block_on(Markers::load(&DirMarkerSource::default())).unwrap(),
+    );
+}
+
+fn loading_app(source: EmbeddedMarkerSource) -> App {
+    let mut app = App::new();
+    // The resources of the other plugins read by the markers systems' run conditions
+    app.add_plugins((MinimalPlugins, MarkersPlugin::new(source)))
+        .init_resource::<EguiHoverStatus>()
+        .init_resource::<Input<KeyCode>>()
+        .init_resource::<MapType>()
+        .init_resource::<Ruler>();
+    app
+}
+
+/// Updates the app until the loading is over, the systems running once the markers are ready
+/// aren't run
+fn finish_loading(app: &mut App) -> AppState {
+    for _ in 0..1_000 {
+        app.update();
+        if let Some(state) = &app.world.resource::<NextState<AppState>>().0 {
+            return *state;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    panic!("the markers are still loading");
+}
+
+#[test]
+fn the_markers_are_loaded_in_the_background() {
+    let mut app = loading_app(embedded_fixture());
+    assert_eq!(finish_loading(&mut app), AppState::Ready);
+    let progress = app.world.resource::<MarkersProgress>();
+    assert!(progress.errors.is_empty());
+    for map_type in MapType::iter() {
+        assert_eq!(progress.progress(*map_type), 1.0);
+    }
+    assert!(app
+        .world
+        .resource::<Markers>()
+        .location_marker(MapType::SKY, "1")
+        .is_some());
+}
+
+#[test]
+fn loading_errors_are_reported() {
+    let mut source = embedded_fixture();
+    source.insert(
+        MapType::DEPTHS,
+        MarkersFileKind::Locations,
+        b"[{".as_slice(),
+    );
+    let mut app = loading_app(source);
+    assert_eq!(finish_loading(&mut app), AppState::Failed);
+    let progress = app.world.resource::<MarkersProgress>();
+    assert_eq!(progress.errors.len(), 1);
+    assert!(
+        progress.errors[0].contains("depths locations"),
+        "{:?}",
+        progress.errors
+    );
+    assert_eq!(progress.progress(MapType::DEPTHS), 0.5);
+    assert!(app
+        .world
+        .resource::<Markers>()
+        .materials(MapType::DEPTHS)
+        .is_empty());
+}
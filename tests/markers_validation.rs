@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use totk_map::{
+    marker_source::MarkersFileKind,
+    resources::MapType,
+    validation::{MarkersFile, MarkersValidation, Severity},
+};
+
+fn file(map_type: MapType, kind: MarkersFileKind, content: &str) -> MarkersFile {
+    MarkersFile {
+        map_type,
+        kind,
+        path: PathBuf::from(format!("{map_type}/{kind}.json")),
+        content: content.to_string(),
+    }
+}
+
+/// The severity, line and message of every issue
+fn issues(files: &[MarkersFile]) -> Vec<(Severity, Option<usize>, String)> {
+    MarkersValidation::validate(files)
+        .issues
+        .into_iter()
+        .map(|issue| (issue.severity, issue.line, issue.message))
+        .collect()
+}
+
+const LOCATIONS: &str = r#"[
+    {
+        "name": "Shrine",
+        "layers": [
+            {
+                "icon": {"url": "shrine.png", "width": 23, "height": 27},
+                "minZoom": 5,
+                "maxZoom": 3,
+                "markers": [
+                    {"coords": [10.0, 20.0], "elv": 1.0, "id": "A"},
+                    {"coords": [7000.0, 20.0], "elv": 1.0, "id": "B", "color": "red"},
+                    {"coords": [10.0, 20.0], "elv": 1.0, "id": "A"}
+                ]
+            },
+            {"markers": []}
+        ]
+    }
+]"#;
+
+#[test]
+fn the_shipped_markers_load() {
+    let files = MarkersFile::read_all(Path::new("assets/markers")).unwrap();
+    let validation = MarkersValidation::validate(&files);
+    assert!(!validation.has_errors(), "{validation}");
+}
+
+#[test]
+fn reports_the_issues_with_their_lines() {
+    assert_eq!(
+        issues(&[file(MapType::SKY, MarkersFileKind::Locations, LOCATIONS)]),
+        vec![
+            (
+                Severity::Warning,
+                Some(11),
+                "unknown field color".to_string()
+            ),
+            (
+                Severity::Error,
+                Some(5),
+                "minZoom 5 is greater than maxZoom 3".to_string()
+            ),
+            (
+                Severity::Error,
+                Some(11),
+                "coordinates [7000, 20] are outside of the map, from -6000 to 6000".to_string()
+            ),
+            (
+                Severity::Error,
+                Some(12),
+                "duplicate id A, first at sky/locations.json:10".to_string()
+            ),
+            (
+                Severity::Warning,
+                Some(15),
+                "empty layer in Shrine".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reports_the_syntax_errors_with_their_position() {
+    let files = [file(
+        MapType::DEPTHS,
+        MarkersFileKind::Materials,
+        "[\n    {\"name\": \"Ore\", \"markerCoords\": [[1, 2, 3],]}\n]",
+    )];
+    let validation = MarkersValidation::validate(&files);
+    assert_eq!(validation.errors, 1);
+    let issue = &validation.issues[0];
+    assert_eq!((issue.line, issue.column), (Some(2), Some(48)));
+    assert_eq!(
+        issue.to_string(),
+        "depths/materials.json:2:48: error: trailing comma"
+    );
+}
+
+#[test]
+fn reports_the_categories_on_several_maps() {
+    let materials = r#"[{"name": "Shrine", "markerCoords": [[1, 2, 3]]}]"#;
+    let files = [
+        file(MapType::SKY, MarkersFileKind::Locations, LOCATIONS),
+        file(MapType::SURFACE, MarkersFileKind::Materials, materials),
+        file(MapType::SKY, MarkersFileKind::Materials, materials),
+    ];
+    let validation = MarkersValidation::validate(&files);
+    let issue = validation.issues.last().unwrap();
+    assert_eq!(issue.severity, Severity::Warning);
+    assert_eq!(issue.line, Some(2));
+    assert_eq!(
+        issue.message,
+        "category Shrine is on several maps: sky, surface"
+    );
+}
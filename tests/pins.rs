@@ -0,0 +1,23 @@
+use bevy::prelude::Vec2;
+use totk_map::resources::{MapType, Pins};
+
+#[test]
+fn pins_are_given_an_id_once_added() {
+    let mut pins = Pins::default();
+    // Cancelled before being saved
+    let cancelled_pin = pins.new_pin(MapType::SURFACE, Vec2::ZERO, "flag.png".to_string());
+    let mut pin = pins.new_pin(MapType::SURFACE, Vec2::ONE, "flag.png".to_string());
+    assert_eq!(cancelled_pin.id, pin.id);
+
+    pins.upsert(pin.clone());
+    pins.upsert(pins.new_pin(MapType::SKY, Vec2::ONE, "star.png".to_string()));
+    pin.label = "Shrine".to_string();
+    pins.upsert(pin);
+
+    let surface_pins = pins.pins(MapType::SURFACE).collect::<Vec<_>>();
+    assert_eq!(surface_pins.len(), 1);
+    assert_eq!(surface_pins[0].label, "Shrine");
+    let sky_pins = pins.pins(MapType::SKY).collect::<Vec<_>>();
+    assert_eq!(sky_pins.len(), 1);
+    assert_ne!(sky_pins[0].id, surface_pins[0].id);
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::Vec2;
+use totk_map::{
+    resources::{Annotations, MapType, Ruler},
+    ruler::{elevation_difference, format_distance, segment_lengths, total_length},
+    types::RulerPoint,
+};
+
+fn point(x: f32, y: f32, elevation: Option<f32>) -> RulerPoint {
+    RulerPoint {
+        pos: Vec2::new(x, y),
+        elevation,
+    }
+}
+
+#[test]
+fn distances_are_measured_along_the_segments() {
+    let points = [
+        point(0.0, 0.0, Some(10.0)),
+        point(300.0, 400.0, None),
+        point(300.0, 1_400.0, Some(-40.0)),
+    ];
+    assert_eq!(
+        segment_lengths(&points, MapType::SURFACE),
+        vec![500.0, 1_000.0]
+    );
+    assert_eq!(total_length(&points, MapType::SURFACE), 1_500.0);
+    assert_eq!(elevation_difference(&points), Some(-50.0));
+
+    assert_eq!(total_length(&points[..1], MapType::SURFACE), 0.0);
+    assert_eq!(elevation_difference(&points[..1]), None);
+    // The last point didn't snap to a marker
+    assert_eq!(elevation_difference(&points[..2]), None);
+}
+
+#[test]
+fn distances_are_formatted_in_meters_then_kilometers() {
+    assert_eq!(format_distance(0.0), "0 m");
+    assert_eq!(format_distance(512.4), "512 m");
+    assert_eq!(format_distance(1_500.0), "1.50 km");
+}
+
+#[test]
+fn the_ruler_restarts_on_another_map() {
+    let mut ruler = Ruler::default();
+    ruler.add_point(MapType::SURFACE, point(0.0, 0.0, None));
+    ruler.add_point(MapType::SURFACE, point(1.0, 0.0, None));
+    ruler.remove_last_point();
+    assert_eq!(ruler.points(), [point(0.0, 0.0, None)]);
+
+    ruler.add_point(MapType::DEPTHS, point(2.0, 0.0, None));
+    assert_eq!(ruler.map_type(), Some(MapType::DEPTHS));
+    assert_eq!(ruler.points(), [point(2.0, 0.0, None)]);
+
+    ruler.clear();
+    assert!(ruler.points().is_empty());
+}
+
+#[test]
+fn annotations_are_kept_per_map() {
+    let mut annotations = Annotations::default();
+    let points = vec![point(0.0, 0.0, None), point(1.0, 0.0, None)];
+    let glide = annotations.add(MapType::SKY, "Glide".to_string(), points.clone());
+    annotations.add(MapType::SURFACE, "Ride".to_string(), points);
+    assert_ne!(glide, annotations.add(MapType::SKY, String::new(), vec![]));
+
+    assert_eq!(annotations.annotations(MapType::SKY).count(), 2);
+    annotations.remove(glide);
+    assert_eq!(annotations.annotations(MapType::SKY).count(), 1);
+    assert_eq!(
+        annotations
+            .annotations(MapType::SURFACE)
+            .map(|annotation| annotation.label.as_str())
+            .collect::<Vec<_>>(),
+        ["Ride"]
+    );
+}
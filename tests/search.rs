@@ -0,0 +1,83 @@
+use totk_map::{
+    resources::{Lod, MapType, Markers},
+    search::{fuzzy_score, SearchIndex},
+    types::Location,
+};
+
+const VILLAGES: &str = r#"{
+    "name": "Village",
+    "layers": [{
+        "minZoom": 1,
+        "maxZoom": 4,
+        "markers": [{ "id": "hateno", "name": "Hateno Village", "coords": [100.0, -200.0], "elv": 0.0 }]
+    }, {
+        "minZoom": 6,
+        "markers": [{ "id": "kakariko", "name": "Kakariko Village", "coords": [300.0, -400.0], "elv": 0.0 }]
+    }, {
+        "markers": [{ "id": "lurelin", "name": "Lurelin Village", "coords": [500.0, -600.0], "elv": 0.0 }]
+    }]
+}"#;
+
+fn search_index() -> SearchIndex {
+    let mut markers = Markers::default();
+    *markers.locations_mut(MapType::SURFACE) =
+        vec![serde_json::from_str::<Location>(VILLAGES).unwrap()];
+    SearchIndex::from_markers(&markers)
+}
+
+fn labels(search_index: &SearchIndex, query: &str) -> Vec<String> {
+    search_index
+        .search(query)
+        .into_iter()
+        .map(|index| search_index.entries()[index].label.clone())
+        .collect()
+}
+
+#[test]
+fn exact_prefixes_and_word_starts_score_higher() {
+    assert_eq!(fuzzy_score("shrine", "shrine"), Some(1_000));
+    assert_eq!(fuzzy_score("shr", "rhs"), None);
+    assert_eq!(fuzzy_score("shrines", "shrine"), None);
+
+    let prefix = fuzzy_score("shr", "shrine").unwrap();
+    let word_start = fuzzy_score("shr", "great shrine").unwrap();
+    let scattered = fuzzy_score("shr", "ash river").unwrap();
+    assert!(prefix > word_start, "{prefix} <= {word_start}");
+    assert!(word_start > scattered, "{word_start} <= {scattered}");
+    // Longer candidates are slightly penalized
+    assert!(fuzzy_score("shr", "shrine").unwrap() > fuzzy_score("shr", "shrine of light").unwrap());
+}
+
+#[test]
+fn results_are_sorted_best_first() {
+    let search_index = search_index();
+    assert!(search_index.search("  ").is_empty());
+    assert!(search_index.search("gerudo").is_empty());
+
+    assert_eq!(
+        labels(&search_index, "village"),
+        [
+            "Village (all)",
+            "Hateno Village (hateno)",
+            "Kakariko Village (kakariko)",
+            "Lurelin Village (lurelin)",
+        ]
+    );
+    assert_eq!(
+        labels(&search_index, "kak")[0],
+        "Kakariko Village (kakariko)"
+    );
+    assert_eq!(labels(&search_index, "lrln"), ["Lurelin Village (lurelin)"]);
+}
+
+#[test]
+fn the_markers_are_flown_to_at_a_lod_displaying_them() {
+    let search_index = search_index();
+    let lod = |query: &str| search_index.entries()[search_index.search(query)[0]].lod;
+    // Only displayed up to lod 4
+    assert_eq!(lod("hateno"), Lod::new(4));
+    // Only displayed from lod 6
+    assert_eq!(lod("kakariko"), Lod::new(6));
+    assert_eq!(lod("lurelin"), Lod::new(5));
+    assert_eq!(lod("village"), MapType::SURFACE.min_lod());
+}
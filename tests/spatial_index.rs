@@ -0,0 +1,121 @@
+use bevy::{math::Rect, prelude::Vec2};
+use totk_map::spatial::QuadTree;
+
+/// A grid of `size` x `size` points spaced by 10 units, the items are the points' indexes
+fn grid(size: u32) -> QuadTree<u32> {
+    (0..size * size)
+        .map(|index| {
+            let pos = Vec2::new((index % size) as f32, (index / size) as f32) * 10.0;
+            (pos, index)
+        })
+        .collect()
+}
+
+fn sorted(mut items: Vec<u32>) -> Vec<u32> {
+    items.sort_unstable();
+    items
+}
+
+#[test]
+fn empty_tree() {
+    let quad_tree = QuadTree::<u32>::new(Rect::new(0.0, 0.0, 100.0, 100.0));
+    assert!(quad_tree.is_empty());
+    assert!(quad_tree
+        .query_rect(Rect::new(0.0, 0.0, 100.0, 100.0))
+        .is_empty());
+    assert_eq!(quad_tree.nearest(Vec2::ZERO, f32::INFINITY), None);
+}
+
+#[test]
+fn query_rect_includes_bounds() {
+    let quad_tree = grid(100);
+    assert_eq!(quad_tree.len(), 10_000);
+
+    let items = quad_tree.query_rect(Rect::new(10.0, 20.0, 30.0, 30.0));
+    assert_eq!(sorted(items), vec![201, 202, 203, 301, 302, 303]);
+}
+
+#[test]
+fn query_rect_matches_full_scan() {
+    let quad_tree = grid(100);
+    let rect = Rect::new(123.0, 456.0, 789.0, 654.0);
+
+    let expected = (0..10_000)
+        .filter(|index| {
+            let pos = Vec2::new((index % 100) as f32, (index / 100) as f32) * 10.0;
+            rect.contains(pos)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(sorted(quad_tree.query_rect(rect)), expected);
+}
+
+#[test]
+fn query_rect_outside_of_the_points() {
+    let quad_tree = grid(10);
+    assert!(quad_tree
+        .query_rect(Rect::new(-50.0, -50.0, -1.0, -1.0))
+        .is_empty());
+}
+
+#[test]
+fn nearest() {
+    let quad_tree = grid(100);
+
+    assert_eq!(quad_tree.nearest(Vec2::new(12.0, 18.0), 10.0), Some(201));
+    assert_eq!(quad_tree.nearest(Vec2::new(-30.0, -40.0), 100.0), Some(0));
+    assert_eq!(quad_tree.nearest(Vec2::new(-30.0, -40.0), 49.0), None);
+    assert_eq!(
+        quad_tree.nearest(Vec2::new(5_000.0, 5_000.0), f32::INFINITY),
+        Some(9999)
+    );
+}
+
+#[test]
+fn nearest_by_skipped_and_moved_items() {
+    let quad_tree = grid(100);
+    let pos = Vec2::new(12.0, 18.0);
+
+    // The closest item is skipped
+    assert_eq!(
+        quad_tree.nearest_by(pos, 10.0, 0.0, |item_pos, index| {
+            (index != 201).then_some(item_pos)
+        }),
+        Some(202)
+    );
+    // An item moved next to `pos` from a node that would be pruned without the margin
+    let moved = |item_pos, index| Some(if index == 9_999 { pos } else { item_pos });
+    assert_eq!(quad_tree.nearest_by(pos, 10.0, 1_500.0, moved), Some(9_999));
+    assert_eq!(quad_tree.nearest_by(pos, 10.0, 0.0, moved), Some(201));
+}
+
+#[test]
+fn insert_outside_of_the_initial_bounds() {
+    let mut quad_tree = QuadTree::new(Rect::new(0.0, 0.0, 10.0, 10.0));
+    for index in 0..1_000 {
+        quad_tree.insert(Vec2::splat(index as f32), index);
+    }
+
+    assert_eq!(quad_tree.len(), 1_000);
+    assert_eq!(
+        sorted(quad_tree.query_rect(Rect::new(500.0, 500.0, 502.0, 502.0))),
+        vec![500, 501, 502]
+    );
+    assert_eq!(
+        quad_tree.nearest(Vec2::splat(2_000.0), f32::INFINITY),
+        Some(999)
+    );
+}
+
+#[test]
+fn duplicated_positions() {
+    // More points than a node can hold at the same position must not split forever
+    let quad_tree = (0..1_000)
+        .map(|index| (Vec2::new(1.0, 2.0), index))
+        .collect::<QuadTree<_>>();
+
+    assert_eq!(
+        quad_tree.query_rect(Rect::new(0.0, 0.0, 5.0, 5.0)).len(),
+        1_000
+    );
+    assert!(quad_tree.nearest(Vec2::ZERO, 3.0).is_some());
+}
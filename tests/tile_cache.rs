@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use totk_map::{
+    maps::fallback_tile,
+    resources::{Lod, MapType, TileBudget, TileCache, TileKey, TILE_MEMORY_BYTES},
+};
+
+fn key(x_idx: u32) -> TileKey {
+    TileKey {
+        map_type: MapType::SURFACE,
+        lod: MapType::SURFACE.max_lod(),
+        x_idx,
+        y_idx: 0,
+    }
+}
+
+fn entity(index: u32) -> Entity {
+    Entity::from_raw(index)
+}
+
+#[test]
+fn max_tiles_from_megabytes() {
+    assert_eq!(TileBudget::Tiles(12).max_tiles(), 12);
+    assert_eq!(
+        TileBudget::Megabytes(256).max_tiles(),
+        256 * 1024 * 1024 / TILE_MEMORY_BYTES
+    );
+    assert_eq!(TileBudget::Megabytes(0).max_tiles(), 0);
+}
+
+#[test]
+fn hits_and_misses() {
+    let mut tile_cache = TileCache::new(TileBudget::Tiles(4));
+    tile_cache.insert(key(0), entity(0));
+    tile_cache.insert(key(1), entity(1));
+    assert_eq!(tile_cache.peek(key(0)), Some(entity(0)));
+    assert_eq!(tile_cache.peek(key(2)), None);
+
+    // Still visible since the previous frame
+    tile_cache.next_frame();
+    tile_cache.touch(key(0));
+    tile_cache.touch(key(1));
+    tile_cache.next_frame();
+    tile_cache.touch(key(0));
+    assert_eq!(tile_cache.stats().hits, 0);
+
+    // Shown again after being hidden
+    tile_cache.next_frame();
+    tile_cache.touch(key(1));
+    tile_cache.touch(key(1));
+
+    let stats = tile_cache.stats();
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.misses, 2);
+    assert!(tile_cache.contains(key(0)));
+    assert!(!tile_cache.contains(key(2)));
+}
+
+#[test]
+fn evicts_least_recently_visible_first() {
+    let mut tile_cache = TileCache::new(TileBudget::Tiles(2));
+    for index in 0..3 {
+        tile_cache.insert(key(index), entity(index));
+    }
+    tile_cache.next_frame();
+    // The first tile is visible again, so the second is now the least recently visible
+    tile_cache.touch(key(0));
+    tile_cache.next_frame();
+
+    assert_eq!(tile_cache.evict(), vec![(key(1), entity(1))]);
+    assert_eq!(tile_cache.len(), 2);
+    assert!(tile_cache.contains(key(0)));
+    assert!(tile_cache.contains(key(2)));
+    assert_eq!(tile_cache.stats().evictions, 1);
+}
+
+#[test]
+fn visible_tiles_are_never_evicted() {
+    let mut tile_cache = TileCache::new(TileBudget::Tiles(1));
+    tile_cache.next_frame();
+    for index in 0..3 {
+        tile_cache.insert(key(index), entity(index));
+    }
+    // All the tiles were inserted during the current frame
+    assert!(tile_cache.evict().is_empty());
+    assert_eq!(tile_cache.len(), 3);
+
+    tile_cache.next_frame();
+    tile_cache.touch(key(1));
+    assert_eq!(
+        tile_cache.evict(),
+        vec![(key(0), entity(0)), (key(2), entity(2))]
+    );
+    assert!(tile_cache.contains(key(1)));
+}
+
+#[test]
+fn prefetched_tiles_are_evicted_first() {
+    let mut tile_cache = TileCache::new(TileBudget::Tiles(2));
+    tile_cache.insert(key(0), entity(0));
+    tile_cache.next_frame();
+    tile_cache.insert_prefetched(key(1), entity(1));
+    tile_cache.insert(key(2), entity(2));
+    // The prefetched tile loaded in the current frame doesn't prevent the eviction of the others
+    assert_eq!(tile_cache.evict(), vec![(key(0), entity(0))]);
+
+    tile_cache.next_frame();
+    tile_cache.insert(key(3), entity(3));
+    assert_eq!(tile_cache.evict(), vec![(key(1), entity(1))]);
+    assert_eq!(tile_cache.stats().misses, 4);
+}
+
+#[test]
+fn pinned_tiles_are_out_of_the_budget() {
+    let mut tile_cache = TileCache::new(TileBudget::Tiles(1));
+    let base_key = tile(0, 0, 0);
+    tile_cache.insert_pinned(base_key, entity(0));
+    tile_cache.insert(key(1), entity(1));
+    tile_cache.insert(key(2), entity(2));
+    assert_eq!((tile_cache.len(), tile_cache.pinned_len()), (2, 1));
+
+    tile_cache.next_frame();
+    tile_cache.next_frame();
+    assert_eq!(tile_cache.evict(), vec![(key(1), entity(1))]);
+    assert_eq!(tile_cache.peek(base_key), Some(entity(0)));
+    assert!(tile_cache.contains(base_key));
+    assert_eq!(tile_cache.stats().misses, 3);
+}
+
+fn tile(lod: u32, x_idx: u32, y_idx: u32) -> TileKey {
+    TileKey {
+        map_type: MapType::DEPTHS,
+        lod: Lod::new(lod),
+        x_idx,
+        y_idx,
+    }
+}
+
+#[test]
+fn parents_up_to_the_lowest_lod() {
+    assert_eq!(tile(3, 5, 2).parent(), Some(tile(2, 2, 1)));
+    assert_eq!(tile(1, 1, 0).parent(), Some(tile(0, 0, 0)));
+    assert_eq!(tile(0, 0, 0).parent(), None);
+}
+
+#[test]
+fn falls_back_to_the_closest_loaded_ancestor() {
+    let loaded = [tile(0, 0, 0), tile(2, 3, 1)];
+    let is_loaded = |key| loaded.contains(&key);
+
+    // The parent at lod 3 isn't loaded
+    assert_eq!(
+        fallback_tile(tile(4, 13, 6), is_loaded),
+        Some(tile(2, 3, 1))
+    );
+    assert_eq!(fallback_tile(tile(4, 2, 6), is_loaded), Some(tile(0, 0, 0)));
+    assert_eq!(fallback_tile(tile(0, 0, 0), is_loaded), None);
+    assert_eq!(fallback_tile(tile(4, 2, 6), |_| false), None);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::TempDir;
+use totk_map::resources::{Lod, MapType, TileFormat, TileLayout};
+
+#[test]
+fn default_layout() {
+    let layout = TileLayout::default();
+    assert_eq!(
+        layout.path(MapType::SURFACE, Lod::new(3), 5, 2),
+        PathBuf::from("tiles/surface/3/5_2.jpg")
+    );
+    assert_eq!(
+        MapType::DEPTHS.tile_path(Lod::new(6), 63, 0),
+        PathBuf::from("tiles/depths/6/63_0.jpg")
+    );
+}
+
+#[test]
+fn xyz_layout() {
+    let layout = TileLayout {
+        template: TileLayout::XYZ_TEMPLATE.to_string(),
+        format: Some(TileFormat::WebP),
+    };
+    assert_eq!(
+        layout.path(MapType::SKY, Lod::new(4), 7, 11),
+        PathBuf::from("tiles/sky/4/7/11.webp")
+    );
+
+    let layout = TileLayout {
+        template: "xyz/{z}/{x}/{y}.png".to_string(),
+        format: None,
+    };
+    assert_eq!(
+        layout.path(MapType::SKY, Lod::new(1), 0, 1),
+        PathBuf::from("xyz/1/0/1.png")
+    );
+}
+
+#[test]
+fn formats_are_parsed_from_their_extension() {
+    for format in TileFormat::iter() {
+        assert_eq!(format.extension().parse::<TileFormat>(), Ok(*format));
+    }
+    assert_eq!("jpeg".parse::<TileFormat>(), Ok(TileFormat::Jpeg));
+    assert!("gif".parse::<TileFormat>().is_err());
+}
+
+#[test]
+fn detects_the_preferred_format_present() {
+    let assets_dir = TempDir::new("layout");
+    let layout = TileLayout {
+        template: TileLayout::DEFAULT_TEMPLATE.to_string(),
+        format: None,
+    };
+    assert_eq!(layout.detect_format(MapType::SURFACE, &assets_dir), None);
+
+    fs::create_dir_all(assets_dir.join("tiles/surface/0")).unwrap();
+    for extension in ["jpg", "webp"] {
+        fs::write(
+            assets_dir.join(format!("tiles/surface/0/0_0.{extension}")),
+            [],
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        layout.detect_format(MapType::SURFACE, &assets_dir),
+        Some(TileFormat::WebP)
+    );
+    assert_eq!(layout.detect_format(MapType::SKY, &assets_dir), None);
+}
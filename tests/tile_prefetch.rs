@@ -0,0 +1,129 @@
+use bevy::{math::Rect, prelude::*};
+use totk_map::{
+    maps::{prefetched_tiles, tile_ranges},
+    resources::{Lod, MapType, TileKey, TilePrefetch},
+};
+
+fn tile(lod: u32, x_idx: u32, y_idx: u32) -> TileKey {
+    TileKey {
+        map_type: MapType::SURFACE,
+        lod: Lod::new(lod),
+        x_idx,
+        y_idx,
+    }
+}
+
+/// Inside the tile (1, 1) at lod 2, whose tiles are 3000 pixels wide
+fn viewport() -> Rect {
+    Rect::from_center_size(tile(2, 1, 1).center(), Vec2::splat(1000.0))
+}
+
+fn prefetch(ring: u32) -> TilePrefetch {
+    TilePrefetch {
+        ring,
+        look_ahead: 0.5,
+        ..default()
+    }
+}
+
+#[test]
+fn tile_centers_and_ranges() {
+    assert_eq!(tile(0, 0, 0).center(), Vec2::ZERO);
+    assert_eq!(tile(2, 1, 1).center(), Vec2::new(-1500.0, 1500.0));
+    assert_eq!(
+        tile_ranges(MapType::SURFACE, Lod::new(2), viewport()),
+        (1..=1, 1..=1)
+    );
+    assert_eq!(
+        tile_ranges(MapType::SURFACE, Lod::new(3), viewport()),
+        (2..=3, 2..=3)
+    );
+}
+
+#[test]
+fn ring_around_the_visible_tiles() {
+    let prefetched = prefetched_tiles(
+        MapType::SURFACE,
+        Lod::new(2),
+        viewport(),
+        Vec2::ZERO,
+        false,
+        &prefetch(1),
+    );
+    assert_eq!(prefetched.len(), 8);
+    assert!(!prefetched.contains(&tile(2, 1, 1)));
+    assert!(prefetched.contains(&tile(2, 0, 0)));
+    assert!(prefetched.contains(&tile(2, 2, 2)));
+}
+
+#[test]
+fn ring_is_clamped_to_the_map() {
+    let viewport = Rect::from_center_size(tile(2, 0, 0).center(), Vec2::splat(1000.0));
+    let mut prefetched = prefetched_tiles(
+        MapType::SURFACE,
+        Lod::new(2),
+        viewport,
+        Vec2::ZERO,
+        false,
+        &prefetch(1),
+    );
+    prefetched.sort_by_key(|key| (key.x_idx, key.y_idx));
+    assert_eq!(
+        prefetched,
+        vec![tile(2, 0, 1), tile(2, 1, 0), tile(2, 1, 1)]
+    );
+}
+
+#[test]
+fn look_ahead_along_the_velocity() {
+    // 3000 pixels to the right in half a second
+    let prefetched = prefetched_tiles(
+        MapType::SURFACE,
+        Lod::new(2),
+        viewport(),
+        Vec2::new(6000.0, 0.0),
+        false,
+        &prefetch(0),
+    );
+    assert_eq!(prefetched, vec![tile(2, 2, 1)]);
+}
+
+#[test]
+fn next_lod_when_zooming_in() {
+    let mut prefetched = prefetched_tiles(
+        MapType::SURFACE,
+        Lod::new(2),
+        viewport(),
+        Vec2::ZERO,
+        true,
+        &prefetch(0),
+    );
+    prefetched.sort_by_key(|key| (key.x_idx, key.y_idx));
+    assert_eq!(
+        prefetched,
+        vec![tile(3, 2, 2), tile(3, 2, 3), tile(3, 3, 2), tile(3, 3, 3)]
+    );
+
+    let prefetched = prefetched_tiles(
+        MapType::SURFACE,
+        MapType::SURFACE.max_lod(),
+        viewport(),
+        Vec2::ZERO,
+        true,
+        &prefetch(0),
+    );
+    assert!(prefetched.is_empty());
+}
+
+#[test]
+fn closest_to_the_predicted_viewport_first() {
+    let prefetched = prefetched_tiles(
+        MapType::SURFACE,
+        Lod::new(2),
+        viewport(),
+        Vec2::new(6000.0, 0.0),
+        false,
+        &prefetch(1),
+    );
+    assert_eq!(prefetched.first(), Some(&tile(2, 2, 1)));
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use std::{fs, ops::RangeInclusive};
+
+use common::TempDir;
+use image::{imageops::FilterType, Rgb, RgbImage};
+use totk_map::{
+    resources::{Lod, MapType, TILE_SIZE_PX},
+    tiles::{build_pyramid, child_indexes, merge_tiles, source_tile, PyramidOptions},
+};
+
+const RED: Rgb<u8> = Rgb([255, 0, 0]);
+const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+/// Red on the left half, blue on the right half, big enough for the lod 2
+fn source() -> RgbImage {
+    let size = TILE_SIZE_PX * 4;
+    RgbImage::from_fn(size, size, |x, _| if x < size / 2 { RED } else { BLUE })
+}
+
+#[test]
+fn tiles_cover_their_area_of_the_source() {
+    let source = source();
+    let left = source_tile(&source, Lod::new(1), 0, 1, FilterType::Nearest);
+    let right = source_tile(&source, Lod::new(1), 1, 0, FilterType::Nearest);
+    assert_eq!(left.dimensions(), (TILE_SIZE_PX, TILE_SIZE_PX));
+    assert!(left.pixels().all(|pixel| *pixel == RED));
+    assert!(right.pixels().all(|pixel| *pixel == BLUE));
+
+    // Merged from the lod 1
+    assert_eq!(child_indexes(0, 0), [(0, 0), (1, 0), (0, 1), (1, 1)]);
+    assert_eq!(child_indexes(1, 2)[3], (3, 5));
+    let children = child_indexes(0, 0)
+        .map(|(x_idx, y_idx)| source_tile(&source, Lod::new(1), x_idx, y_idx, FilterType::Nearest));
+    let whole = merge_tiles(&children, FilterType::Nearest);
+    assert_eq!(whole.dimensions(), (TILE_SIZE_PX, TILE_SIZE_PX));
+    assert_eq!(*whole.get_pixel(0, 0), RED);
+    assert_eq!(*whole.get_pixel(TILE_SIZE_PX - 1, TILE_SIZE_PX - 1), BLUE);
+}
+
+#[test]
+fn the_source_is_stretched_to_the_tiles() {
+    // Not a multiple of the lod's tiles
+    let source = RgbImage::from_fn(1001, 501, |x, _| if x < 500 { RED } else { BLUE });
+    let left = source_tile(&source, Lod::new(1), 0, 1, FilterType::Nearest);
+    assert_eq!(left.dimensions(), (TILE_SIZE_PX, TILE_SIZE_PX));
+    assert!(left.pixels().all(|pixel| *pixel == RED));
+    let right = source_tile(&source, Lod::new(1), 1, 1, FilterType::Nearest);
+    assert!(right.pixels().all(|pixel| *pixel == BLUE));
+}
+
+#[test]
+fn builds_the_lods_and_skips_the_up_to_date_tiles() {
+    let dir = TempDir::new("pyramid");
+    let source_path = dir.join("source.png");
+    source().save(&source_path).unwrap();
+    let assets_dir = dir.join("assets");
+    let mut options = PyramidOptions {
+        quality: 50,
+        filter: FilterType::Triangle,
+        skip_up_to_date: true,
+        lods: Some(0..=2),
+    };
+
+    let tiles_nb = (0..=2)
+        .map(|lod| Lod::new(lod).tiles_nb().pow(2) as usize)
+        .sum::<usize>();
+    let mut built_lods = Vec::new();
+    let report = build_pyramid(&source_path, MapType::SKY, &assets_dir, &options, |lod| {
+        built_lods.push(lod);
+    })
+    .unwrap();
+    assert_eq!((report.written, report.skipped), (tiles_nb, 0));
+    assert_eq!(built_lods, (0..=2).rev().map(Lod::new).collect::<Vec<_>>());
+    let last_tile = assets_dir.join(MapType::SKY.tile_path(Lod::new(2), 3, 3));
+    let last_tile = image::open(last_tile).unwrap();
+    assert_eq!(last_tile.width(), TILE_SIZE_PX);
+
+    let report = build_pyramid(&source_path, MapType::SKY, &assets_dir, &options, |_| {}).unwrap();
+    assert_eq!((report.written, report.skipped), (0, tiles_nb));
+
+    // The lod 2 is up to date, so the lod 1 is merged from its tiles, without the source, and the
+    // lod 0 from the lod 1
+    let source_modified = fs::metadata(&source_path).unwrap().modified().unwrap();
+    fs::File::create(&source_path)
+        .unwrap()
+        .set_modified(source_modified)
+        .unwrap();
+    fs::remove_dir_all(assets_dir.join("tiles/sky/0")).unwrap();
+    fs::remove_file(assets_dir.join(MapType::SKY.tile_path(Lod::new(1), 1, 1))).unwrap();
+    let report = build_pyramid(&source_path, MapType::SKY, &assets_dir, &options, |_| {}).unwrap();
+    assert_eq!((report.written, report.skipped), (2, tiles_nb - 2));
+
+    options.skip_up_to_date = false;
+    source().save(&source_path).unwrap();
+    fs::remove_dir_all(assets_dir.join("tiles/sky/2")).unwrap();
+    let report = build_pyramid(&source_path, MapType::SKY, &assets_dir, &options, |_| {}).unwrap();
+    assert_eq!(report.written, tiles_nb);
+}
+
+#[test]
+fn the_source_is_not_upscaled() {
+    let dir = TempDir::new("pyramid_small_source");
+    let source_path = dir.join("source.png");
+    RgbImage::new(TILE_SIZE_PX, TILE_SIZE_PX)
+        .save(&source_path)
+        .unwrap();
+    let options = PyramidOptions {
+        lods: Some(0..=1),
+        ..PyramidOptions::default()
+    };
+
+    let error = build_pyramid(&source_path, MapType::SKY, &dir, &options, |_| {}).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "the source must be at least 1128x1128 pixels for lod 1, it's 564x564"
+    );
+}
+
+#[test]
+fn the_lods_must_be_the_maps() {
+    let dir = TempDir::new("pyramid_lods");
+    let source_path = dir.join("source.png");
+    source().save(&source_path).unwrap();
+    let error = |lods| {
+        let options = PyramidOptions {
+            lods: Some(lods),
+            ..PyramidOptions::default()
+        };
+        build_pyramid(&source_path, MapType::SKY, &dir, &options, |_| {})
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        error(RangeInclusive::new(7, 6)),
+        "no lods to build, the min lod 7 is above the max lod 6"
+    );
+    assert_eq!(
+        error(2..=7),
+        "the lods 2..=7 aren't all sky's, which are 0..=6"
+    );
+    assert!(!dir.join("tiles").exists());
+}